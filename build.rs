@@ -12,5 +12,10 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     let font_out = std::path::Path::new(font_out.as_str());
 
     std::fs::copy(font_source, font_out)?;
+
+    // the web font can't be rasterized, social preview images are drawn with the TTF
+    let ttf_source = format!("{}/Recursive_VF.ttf", env!("RECURSIVE_FONT_DIR"));
+    let ttf_source = std::fs::canonicalize(ttf_source)?;
+    std::fs::copy(ttf_source, format!("{target_dir}/site-font.ttf"))?;
     Ok(())
 }
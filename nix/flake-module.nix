@@ -65,6 +65,14 @@ in
               tag = gitRev;
               config = {
                 Cmd = [ (lib.getExe self'.packages.${name}) ];
+                # traffic is only sent to containers that are ready, durations are in nanoseconds
+                Healthcheck = {
+                  Test = [ "CMD" (lib.getExe self'.packages.${name}) "--check-ready" ];
+                  Interval = 30 * 1000000000;
+                  Timeout = 10 * 1000000000;
+                  StartPeriod = 30 * 1000000000;
+                  Retries = 3;
+                };
               };
             }).overrideAttrs {
               __structuredAttrs = true;
@@ -110,6 +118,8 @@ in
                 find . -type f -name "*.woff2" -exec cp {} $out/. \;
                 # also add a stable output for the variable font
                 ln -s "$out/Recursive_VF_${pkgs.recursive.version}.woff2" "$out/Recursive_VF.woff2"
+                # the TTF is needed for rendering social preview images
+                find . -type f -name "Recursive_VF_${pkgs.recursive.version}.ttf" -exec cp {} $out/Recursive_VF.ttf \;
               '';
               setupHook = lib.getExe (pkgs.writeScriptBin "recursive-webfont-setup" /* sh */ ''
                 addFontEnvVars() {
@@ -144,6 +154,7 @@ in
                 rustc
                 lld
                 makeWrapper
+                brotli
               ]) ++ [
                 tailwindcss
                 craneLib.removeReferencesToRustToolchainHook
@@ -162,15 +173,22 @@ in
                 mkdir -p $out/bin
                 cp target/release/${name} $out/bin/
                 cp -r target/site $out/bin/
+                # hashes of the fingerprinted `pkg` files, read from next to the binary
+                cp target/release/hash.txt $out/bin/
 
                 find $out/bin -type f -exec wasm-opt -Oz -g '{}' \; -exec strip -s -v '{}' \; 2>/dev/null
 
+                # precompressed copies are served to clients accepting them
+                find $out/bin/site/pkg -type f \( -name '*.js' -o -name '*.wasm' -o -name '*.css' \) \
+                  -exec brotli --best --keep '{}' \; -exec gzip --best --keep '{}' \;
+
                 patchelf --shrink-rpath \
                   $out/bin/${name}
 
                 wrapProgram $out/bin/${name} \
                   --set LEPTOS_SITE_ROOT $out/bin/site \
                   --set LEPTOS_ENV PROD \
+                  --set LEPTOS_HASH_FILES true \
                   --set LEPTOS_SITE_ADDR "0.0.0.0:3000"
               '';
               meta.mainProgram = name;
@@ -191,6 +209,13 @@ in
             site.composeProject.services.backend = {
               image = "${name}:${gitRev}";
               ports = [ "${toString listenPort}:${toString listenPort}" ];
+              healthcheck = {
+                test = [ "CMD" (lib.getExe package) "--check-ready" ];
+                interval = "30s";
+                timeout = "10s";
+                start_period = "30s";
+                retries = 3;
+              };
             };
 
             # Rust package
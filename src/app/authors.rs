@@ -0,0 +1,311 @@
+use super::ErrorPage;
+use super::posts::PostId;
+use leptos::prelude::*;
+use leptos::server_fn::{
+    codec::JsonEncoding,
+    error::{FromServerFnError, ServerFnErrorErr},
+};
+use leptos_meta::*;
+use leptos_router::hooks::use_params_map;
+use serde::{Deserialize, Serialize};
+
+/// Avatar shown for authors without an avatar image
+#[cfg(feature = "ssr")]
+const FALLBACK_AVATAR: &str = "/assets/avatar.svg";
+
+/// An author in the authors registry
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Author {
+    /// key of the author in the registry, used in post metadata and in `/authors/:id`
+    #[serde(default)]
+    pub id: String,
+    pub name: String,
+    /// URL of the avatar image, `/assets/<id>.webp` if not given
+    #[serde(default)]
+    pub avatar: String,
+    #[serde(default)]
+    pub bio: String,
+    #[serde(default)]
+    pub links: Vec<AuthorLink>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorLink {
+    pub label: String,
+    pub url: String,
+}
+
+/// A post listed on the page of an author
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthoredPost {
+    pub id: PostId,
+    pub title: String,
+    pub date: chrono::NaiveDate,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct AuthorProfile {
+    pub author: Author,
+    pub posts: Vec<AuthoredPost>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum AuthorLoadError {
+    NotFound,
+    RegistryReadFailed(String),
+    RegistryParseFailed(String),
+    PostsLoadFailed(String),
+    ServerFn(ServerFnErrorErr),
+}
+impl std::fmt::Display for AuthorLoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NotFound => write!(f, "Author doesn't exist"),
+            Self::RegistryReadFailed(s) => write!(f, "Reading authors registry failed: {s}"),
+            Self::RegistryParseFailed(s) => write!(f, "Parsing authors registry failed: {s}"),
+            Self::PostsLoadFailed(s) => write!(f, "Loading posts failed: {s}"),
+            Self::ServerFn(e) => write!(f, "Server function failed: {e}"),
+        }
+    }
+}
+impl std::error::Error for AuthorLoadError {}
+impl AuthorLoadError {
+    /// HTTP status code to respond with when loading an author fails
+    #[cfg(feature = "ssr")]
+    pub fn status_code(&self) -> actix_web::http::StatusCode {
+        use actix_web::http::StatusCode;
+        match self {
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::RegistryReadFailed(_)
+            | Self::RegistryParseFailed(_)
+            | Self::PostsLoadFailed(_)
+            | Self::ServerFn(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn heading(&self) -> &'static str {
+        match self {
+            Self::NotFound => "Not Found",
+            _ => "Server Error",
+        }
+    }
+
+    fn friendly_message(&self) -> &'static str {
+        match self {
+            Self::NotFound => "This author doesn't exist.",
+            Self::RegistryReadFailed(_)
+            | Self::RegistryParseFailed(_)
+            | Self::PostsLoadFailed(_) => {
+                "The details of this author can't be shown right now. It will be fixed soon."
+            }
+            Self::ServerFn(_) => "Something went wrong while loading this author. Try again later.",
+        }
+    }
+}
+impl FromServerFnError for AuthorLoadError {
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        Self::ServerFn(value)
+    }
+}
+
+/// Authors registry, read from `authors.toml` in the site root
+///
+/// A missing registry is the same as an empty one, every author gets the
+/// fallback profile then.
+#[cfg(feature = "ssr")]
+pub fn load_authors(
+    site_root: &str,
+) -> Result<std::collections::HashMap<String, Author>, AuthorLoadError> {
+    use std::collections::HashMap;
+    use std::io::ErrorKind;
+
+    let registry = match std::fs::read_to_string(format!("{site_root}/authors.toml")) {
+        Ok(registry) => registry,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(AuthorLoadError::RegistryReadFailed(e.to_string())),
+    };
+    let mut authors: HashMap<String, Author> = toml::from_str(&registry)
+        .map_err(|e| AuthorLoadError::RegistryParseFailed(e.to_string()))?;
+
+    for (id, author) in authors.iter_mut() {
+        author.id = id.clone();
+        if author.avatar.is_empty() {
+            author.avatar = format!("/assets/{id}.webp");
+        }
+        author.avatar = checked_avatar(&author.avatar, site_root);
+    }
+    Ok(authors)
+}
+
+/// Profiles of the authors with the given IDs, in the same order
+///
+/// Authors missing from the registry are shown with their ID as their name.
+#[cfg(feature = "ssr")]
+pub fn resolve_authors(ids: &[String], site_root: &str) -> Result<Vec<Author>, AuthorLoadError> {
+    let mut registry = load_authors(site_root)?;
+    Ok(ids
+        .iter()
+        .map(|id| {
+            registry.remove(id).unwrap_or_else(|| {
+                tracing::warn!("author `{id}` is not in the authors registry");
+                Author {
+                    id: id.clone(),
+                    name: id.clone(),
+                    avatar: checked_avatar(&format!("/assets/{id}.webp"), site_root),
+                    bio: String::new(),
+                    links: Vec::new(),
+                }
+            })
+        })
+        .collect())
+}
+
+/// The avatar URL if it points to an existing asset or to another site, the fallback avatar otherwise
+#[cfg(feature = "ssr")]
+fn checked_avatar(url: &str, site_root: &str) -> String {
+    use std::path::{Component, Path};
+
+    let Some(asset) = url.strip_prefix("/assets/") else {
+        return url.to_string();
+    };
+    let asset = Path::new(asset);
+    let exists = asset
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+        && Path::new(site_root).join(asset).is_file();
+    if exists {
+        url.to_string()
+    } else {
+        FALLBACK_AVATAR.to_string()
+    }
+}
+
+#[server]
+pub async fn load_author_profile(author_id: String) -> Result<AuthorProfile, AuthorLoadError> {
+    let result = author_profile(&author_id);
+    if let Err(e) = &result {
+        let resp = expect_context::<leptos_actix::ResponseOptions>();
+        resp.set_status(e.status_code());
+    }
+    result
+}
+
+#[cfg(feature = "ssr")]
+fn author_profile(author_id: &str) -> Result<AuthorProfile, AuthorLoadError> {
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = &config.leptos_options.site_root;
+
+    let author = load_authors(site_root)?
+        .remove(author_id)
+        .ok_or(AuthorLoadError::NotFound)?;
+    let posts = super::posts::list_posts()
+        .map_err(|e| AuthorLoadError::PostsLoadFailed(e.to_string()))?
+        .into_iter()
+        .filter(|(_, metadata)| metadata.authors.iter().any(|id| id == author_id))
+        .map(|(id, metadata)| AuthoredPost {
+            id,
+            title: metadata.title,
+            date: metadata.date,
+        })
+        .collect();
+
+    Ok(AuthorProfile { author, posts })
+}
+
+#[component]
+pub fn Page() -> impl IntoView {
+    let params = use_params_map();
+    let author_id = move || params.read().get("id").unwrap_or_default();
+    let profile = Resource::new(author_id, load_author_profile);
+
+    view! {
+        <Suspense fallback=move || {
+            view! { <p>"Loading..."</p> }
+        }>
+            {move || {
+                profile
+                    .get()
+                    .map(|result| match result {
+                        Ok(profile) => view! { <AuthorProfileView profile /> }.into_any(),
+                        Err(e) => view! { <AuthorError error=e /> }.into_any(),
+                    })
+            }}
+        </Suspense>
+    }
+}
+
+/// Error page shown in place of an author that failed to load
+#[component]
+fn AuthorError(error: AuthorLoadError) -> impl IntoView {
+    // internal error details are only useful while editing the registry
+    let details = cfg!(debug_assertions).then(|| {
+        view! {
+            <details>
+                <summary>"Details"</summary>
+                <code>{error.to_string()}</code>
+            </details>
+        }
+    });
+
+    view! {
+        <ErrorPage heading=error.heading()>
+            <p>{error.friendly_message()}</p>
+            {details}
+        </ErrorPage>
+    }
+}
+
+#[component]
+fn AuthorProfileView(profile: AuthorProfile) -> impl IntoView {
+    let AuthorProfile { author, posts } = profile;
+    let posts = if posts.is_empty() {
+        view! { <p>"No posts yet!"</p> }.into_any()
+    } else {
+        view! {
+            <ul id="author-posts">
+                {posts
+                    .into_iter()
+                    .map(|post| {
+                        view! {
+                            <li>
+                                <a href=format!("/posts/{}", post.id)>{post.title}</a>
+                                <time datetime=post.date.to_string()>{post.date.to_string()}</time>
+                            </li>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </ul>
+        }
+        .into_any()
+    };
+
+    view! {
+        <Title text=author.name.clone() />
+        <Meta name="description" content=format!("Posts by {}", author.name) />
+        <div id="author-profile">
+            <img id="author-avatar" src=author.avatar alt="author avatar" />
+            <h1 class="text-4xl font-extrabold">{author.name}</h1>
+            <p id="author-bio">{author.bio}</p>
+            <ul id="author-links">
+                {author
+                    .links
+                    .into_iter()
+                    .map(|link| {
+                        view! {
+                            <li>
+                                <a href=link.url rel="external noopener noreferrer">
+                                    {link.label}
+                                </a>
+                            </li>
+                        }
+                    })
+                    .collect::<Vec<_>>()}
+            </ul>
+            <hr />
+            <h2 class="text-2xl font-bold">"Posts"</h2>
+            {posts}
+        </div>
+    }
+}
@@ -1,6 +1,6 @@
 use leptos::prelude::*;
 use leptos_icons::Icon;
-use leptos_meta::{Stylesheet, Title, provide_meta_context};
+use leptos_meta::{Title, provide_meta_context};
 use leptos_router::{
     components::{Route, Router, Routes},
     hooks::use_location,
@@ -12,8 +12,79 @@ use leptos_use::{
 };
 
 mod about;
+mod authors;
 mod home;
 mod posts;
+#[cfg(feature = "ssr")]
+mod sitemap;
+
+#[cfg(feature = "ssr")]
+pub use authors::load_authors;
+#[cfg(feature = "ssr")]
+pub use posts::{
+    IMAGE_CACHE_URL, PostId, check_post_links, failing_posts, image_cache_dir, list_posts,
+    preview_image, reload_posts, set_image_cache_dir,
+};
+#[cfg(feature = "ssr")]
+pub use sitemap::{robots_txt, sitemap_xml};
+
+/// Public address of the site, for URLs that have to be absolute
+///
+/// The server takes it from `SITE_URL`, `https://akosnad.dev` by default, and
+/// reads it once on startup. In the browser it's the origin of the page.
+fn site_url() -> String {
+    #[cfg(feature = "ssr")]
+    {
+        static SITE_URL: std::sync::LazyLock<String> = std::sync::LazyLock::new(|| {
+            std::env::var("SITE_URL")
+                .map(|url| url.trim_end_matches('/').to_string())
+                .unwrap_or_else(|_| "https://akosnad.dev".to_string())
+        });
+        SITE_URL.clone()
+    }
+    #[cfg(not(feature = "ssr"))]
+    {
+        window().location().origin().unwrap_or_default()
+    }
+}
+
+/// Leaves clicks on links to the browser on pages of the static export
+///
+/// Exported pages are plain files, without the server functions the router
+/// would call when navigating on the client, so every page is loaded from its
+/// own file instead. Clicks on links are stopped before they reach the router,
+/// which keeps the default action of the browser.
+#[cfg(feature = "hydrate")]
+pub fn follow_links_without_router() {
+    use leptos::wasm_bindgen::{JsCast as _, closure::Closure};
+    use leptos::web_sys::{Element, Event};
+
+    let exported = document()
+        .query_selector("meta[name=static-export]")
+        .ok()
+        .flatten()
+        .is_some();
+    if !exported {
+        return;
+    }
+    let on_click = Closure::<dyn Fn(Event)>::new(|ev: Event| {
+        let in_link = ev
+            .target()
+            .and_then(|target| target.dyn_into::<Element>().ok())
+            .and_then(|element| element.closest("a[href]").ok().flatten())
+            .is_some();
+        if in_link {
+            ev.stop_propagation();
+        }
+    });
+    // the router listens on the window too, capturing runs before it
+    let _ = window().add_event_listener_with_callback_and_bool(
+        "click",
+        on_click.as_ref().unchecked_ref(),
+        true,
+    );
+    on_click.forget();
+}
 
 #[derive(Clone, Debug)]
 struct BackdropProvider {
@@ -70,10 +141,6 @@ pub fn App() -> impl IntoView {
     provide_context(BackdropProvider::default());
 
     view! {
-        // injects a stylesheet into the document <head>
-        // id=leptos means cargo-leptos will hot-reload this stylesheet
-        <Stylesheet id="leptos" href="/pkg/personal-site.css" />
-
         <Title
             formatter=|text: String| {
                 if text.is_empty() {
@@ -98,6 +165,11 @@ pub fn App() -> impl IntoView {
                         view=posts::PostContent
                         ssr=leptos_router::SsrMode::Async
                     />
+                    <Route
+                        path=path!("/authors/:id")
+                        view=authors::Page
+                        ssr=leptos_router::SsrMode::Async
+                    />
                     <Route path=path!("/about") view=about::Page />
                     <Route path=path!("any") view=NotFound />
                 </Routes>
@@ -198,6 +270,22 @@ fn Backdrop() -> impl IntoView {
     }
 }
 
+/// Full page error message with a glitching heading
+#[component]
+fn ErrorPage(
+    #[prop(into)] heading: String,
+    #[prop(optional)] children: Option<Children>,
+) -> impl IntoView {
+    view! {
+        <h1 class="text-5xl">
+            <span class="glitch" data-text=heading.clone()>
+                {heading.clone()}
+            </span>
+        </h1>
+        {children.map(|children| view! { <div class="error-page">{children()}</div> })}
+    }
+}
+
 /// 404 - Not Found
 #[component]
 fn NotFound() -> impl IntoView {
@@ -215,11 +303,5 @@ fn NotFound() -> impl IntoView {
         resp.set_status(actix_web::http::StatusCode::NOT_FOUND);
     }
 
-    view! {
-        <h1 class="text-5xl">
-            <span class="glitch" data-text="Not Found">
-                "Not Found"
-            </span>
-        </h1>
-    }
+    view! { <ErrorPage heading="Not Found" /> }
 }
@@ -0,0 +1,465 @@
+//! Directives in post markdown, e.g. `::youtube{id=...}` or `:::details ... :::`
+//!
+//! Leaf directives take up a single paragraph, container directives wrap every
+//! block between an opening `:::name{...}` and a closing `:::` paragraph.
+//! New embeds are added by writing a handler and listing it in [`HANDLERS`].
+//! Directives with any other name or with malformed attributes fail the post,
+//! pointing at their line and column.
+
+use super::load::PostLoadError;
+use super::render::{Hooks, render, render_children};
+use markdown::mdast::{Node, Paragraph};
+use std::collections::HashMap;
+
+/// A parsed directive, ready to be rendered by its handler
+pub struct Directive {
+    pub name: String,
+    pub attributes: HashMap<String, String>,
+    /// rendered contents of container directives
+    pub inner_html: Option<String>,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Directive {
+    /// Error pointing at the directive in the post source
+    pub fn error(&self, reason: impl Into<String>) -> PostLoadError {
+        PostLoadError::InvalidDirective {
+            reason: reason.into(),
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    pub fn attribute(&self, key: &str) -> Option<&str> {
+        self.attributes.get(key).map(String::as_str)
+    }
+
+    pub fn required_attribute(&self, key: &str) -> Result<&str, PostLoadError> {
+        self.attribute(key).ok_or_else(|| {
+            self.error(format!(
+                "`::{}` is missing the `{key}` attribute",
+                self.name
+            ))
+        })
+    }
+}
+
+/// Renders markdown nodes the way the rest of the post is rendered
+type Render<'a> = dyn FnMut(&Node) -> Result<String, PostLoadError> + 'a;
+
+type Handler = fn(&Directive, &mut Render) -> Result<String, PostLoadError>;
+
+/// Every directive usable in posts, by name
+const HANDLERS: &[(&str, Handler)] = &[
+    ("youtube", youtube),
+    ("figure", figure),
+    ("details", details),
+];
+
+fn handler(name: &str) -> Option<Handler> {
+    HANDLERS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, handler)| *handler)
+}
+
+fn youtube(directive: &Directive, _render: &mut Render) -> Result<String, PostLoadError> {
+    let id = escape(directive.required_attribute("id")?);
+    let title = escape(directive.attribute("title").unwrap_or("YouTube video"));
+    Ok(format!(
+        "<div class=\"embed embed-youtube\"><iframe src=\"https://www.youtube-nocookie.com/embed/{id}\" title=\"{title}\" loading=\"lazy\" allow=\"encrypted-media; picture-in-picture\" allowfullscreen></iframe></div>"
+    ))
+}
+
+/// Same as an image followed by its caption, so it's numbered and gets responsive variants too
+fn figure(directive: &Directive, render: &mut Render) -> Result<String, PostLoadError> {
+    use markdown::mdast::{Break, Image, Text};
+
+    let src = directive.required_attribute("src")?;
+    let caption = directive.required_attribute("caption")?;
+    let image = Image {
+        url: src.to_string(),
+        alt: directive.attribute("alt").unwrap_or(caption).to_string(),
+        title: None,
+        position: None,
+    };
+    render(&Node::Paragraph(Paragraph {
+        children: vec![
+            Node::Image(image),
+            Node::Break(Break { position: None }),
+            Node::Text(Text {
+                value: caption.to_string(),
+                position: None,
+            }),
+        ],
+        position: None,
+    }))
+}
+
+fn details(directive: &Directive, _render: &mut Render) -> Result<String, PostLoadError> {
+    let Some(inner_html) = &directive.inner_html else {
+        return Err(directive.error("`details` has to be a `:::` container directive"));
+    };
+    let summary = html_escape::encode_text(directive.attribute("summary").unwrap_or("Details"));
+    Ok(format!(
+        "<details><summary>{summary}</summary>{inner_html}</details>"
+    ))
+}
+
+fn escape(value: &str) -> std::borrow::Cow<'_, str> {
+    html_escape::encode_double_quoted_attribute(value)
+}
+
+/// Renders a list of sibling nodes, replacing directive paragraphs with the HTML of their handlers
+pub fn render_with_directives(
+    children: &[Node],
+    source: &str,
+    hooks: &mut impl Hooks,
+) -> Result<String, PostLoadError> {
+    let mut html = String::new();
+    let mut i = 0;
+
+    while let Some(node) = children.get(i) {
+        i += 1;
+        let Some((marker, location)) = parse_node(node, source)? else {
+            html.push_str(&render(node, hooks)?);
+            continue;
+        };
+
+        let (name, attributes, inner_html) = match marker {
+            Marker::Leaf { name, attributes } => (name, attributes, None),
+            Marker::Open { name, attributes } => {
+                let start = i;
+                let mut depth = 0;
+                loop {
+                    let Some(node) = children.get(i) else {
+                        return Err(invalid_directive(
+                            format!("`:::{name}` is never closed"),
+                            location,
+                        ));
+                    };
+                    i += 1;
+                    match parse_node(node, source)? {
+                        Some((Marker::Open { .. }, _)) => depth += 1,
+                        Some((Marker::Close, _)) if depth == 0 => break,
+                        Some((Marker::Close, _)) => depth -= 1,
+                        _ => {}
+                    }
+                }
+                let inner = &children[start..i - 1];
+                (name, attributes, Some(render_children(inner, hooks)?))
+            }
+            Marker::Close => {
+                return Err(invalid_directive(
+                    "closing `:::` without an opening directive",
+                    location,
+                ));
+            }
+        };
+
+        let Some(handler) = handler(&name) else {
+            return Err(invalid_directive(
+                format!("unknown directive `{name}`"),
+                location,
+            ));
+        };
+        let directive = Directive {
+            name,
+            attributes,
+            inner_html,
+            line: location.0,
+            column: location.1,
+        };
+        html.push_str(&handler(&directive, &mut |node| render(node, hooks))?);
+    }
+
+    Ok(html)
+}
+
+/// Line and column of a directive in the post source
+type Location = (usize, usize);
+
+enum Marker {
+    Leaf {
+        name: String,
+        attributes: HashMap<String, String>,
+    },
+    Open {
+        name: String,
+        attributes: HashMap<String, String>,
+    },
+    Close,
+}
+
+/// Checks if a node is a directive paragraph, returning the marker and its line and column
+fn parse_node(node: &Node, source: &str) -> Result<Option<(Marker, Location)>, PostLoadError> {
+    let Node::Paragraph(Paragraph {
+        position: Some(position),
+        ..
+    }) = node
+    else {
+        return Ok(None);
+    };
+    let location = (position.start.line, position.start.column);
+    let Some(text) = source.get(position.start.offset..position.end.offset) else {
+        return Ok(None);
+    };
+
+    let mut lines = text.lines();
+    let marker = parse_marker(lines.next().unwrap_or_default().trim_end())
+        .map_err(|reason| invalid_directive(reason, location))?;
+    let Some(marker) = marker else {
+        return Ok(None);
+    };
+    match &marker {
+        Marker::Leaf { name, .. } | Marker::Open { name, .. } if handler(name).is_none() => {
+            return Err(invalid_directive(
+                format!("unknown directive `{name}`"),
+                location,
+            ));
+        }
+        _ => {}
+    }
+    if lines.next().is_some() {
+        return Err(invalid_directive(
+            "directives have to be separated from the surrounding text by blank lines",
+            location,
+        ));
+    }
+    Ok(Some((marker, location)))
+}
+
+fn invalid_directive(reason: impl Into<String>, (line, column): Location) -> PostLoadError {
+    PostLoadError::InvalidDirective {
+        reason: reason.into(),
+        line,
+        column,
+    }
+}
+
+/// Parses `::name{attributes}`, `:::name{attributes}` or `:::`
+///
+/// Lines that don't start like a directive are `Ok(None)`, directives with
+/// broken attributes are an error, so typos aren't published as text.
+fn parse_marker(line: &str) -> Result<Option<Marker>, String> {
+    if line == ":::" {
+        return Ok(Some(Marker::Close));
+    }
+    let (container, rest) = match (line.strip_prefix(":::"), line.strip_prefix("::")) {
+        (Some(rest), _) => (true, rest),
+        (None, Some(rest)) => (false, rest),
+        (None, None) => return Ok(None),
+    };
+
+    let name_len = rest
+        .find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_'))
+        .unwrap_or(rest.len());
+    let (name, rest) = rest.split_at(name_len);
+    if !name.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return Ok(None);
+    }
+
+    let attributes = if rest.is_empty() {
+        HashMap::new()
+    } else if let Some(rest) = rest.strip_prefix('{') {
+        rest.strip_suffix('}')
+            .and_then(parse_attributes)
+            .ok_or_else(|| format!("`{name}` has malformed attributes"))?
+    } else {
+        return Ok(None);
+    };
+
+    let name = name.to_string();
+    Ok(Some(if container {
+        Marker::Open { name, attributes }
+    } else {
+        Marker::Leaf { name, attributes }
+    }))
+}
+
+/// Parses `key=value key="quoted value" flag` pairs
+fn parse_attributes(mut s: &str) -> Option<HashMap<String, String>> {
+    let mut attributes = HashMap::new();
+    loop {
+        s = s.trim_start();
+        if s.is_empty() {
+            return Some(attributes);
+        }
+
+        let key_len = s
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(s.len());
+        let (key, rest) = s.split_at(key_len);
+        if key.is_empty() {
+            return None;
+        }
+
+        let Some(rest) = rest.strip_prefix('=') else {
+            attributes.insert(key.to_string(), String::new());
+            s = rest;
+            continue;
+        };
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => rest.split_at(rest.find(char::is_whitespace).unwrap_or(rest.len())),
+        };
+        attributes.insert(key.to_string(), value.to_string());
+        s = rest;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders markdown the default way, except for directives
+    struct Plain<'a> {
+        source: &'a str,
+    }
+
+    impl Hooks for Plain<'_> {
+        fn node(&mut self, _node: &Node) -> Result<Option<String>, PostLoadError> {
+            Ok(None)
+        }
+
+        fn children(&mut self, children: &[Node]) -> Result<Option<String>, PostLoadError> {
+            render_with_directives(children, self.source, self).map(Some)
+        }
+    }
+
+    fn render_markdown(source: &str) -> Result<String, PostLoadError> {
+        let mdast = markdown::to_mdast(source, &markdown::ParseOptions::gfm()).unwrap();
+        render(&mdast, &mut Plain { source })
+    }
+
+    fn invalid(reason: &str, line: usize, column: usize) -> PostLoadError {
+        PostLoadError::InvalidDirective {
+            reason: reason.to_string(),
+            line,
+            column,
+        }
+    }
+
+    #[test]
+    fn parses_markers() {
+        let Ok(Some(Marker::Leaf { name, attributes })) =
+            parse_marker(r#"::youtube{id=abc title="A video" autoplay}"#)
+        else {
+            panic!("expected a leaf directive");
+        };
+        assert_eq!(name, "youtube");
+        assert_eq!(attributes["id"], "abc");
+        assert_eq!(attributes["title"], "A video");
+        assert_eq!(attributes["autoplay"], "");
+
+        assert!(matches!(
+            parse_marker(":::details"),
+            Ok(Some(Marker::Open { name, attributes })) if name == "details" && attributes.is_empty()
+        ));
+        assert!(matches!(parse_marker(":::"), Ok(Some(Marker::Close))));
+    }
+
+    #[test]
+    fn leaves_text_that_is_not_a_directive() {
+        for line in ["just text", "::", "::1st", "::std::vector", ":: spaced"] {
+            assert!(matches!(parse_marker(line), Ok(None)), "{line}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_attributes() {
+        for line in [
+            r#"::youtube{id="abc}"#,
+            "::youtube{id=abc",
+            "::youtube{=abc}",
+        ] {
+            assert_eq!(
+                parse_marker(line).err().as_deref(),
+                Some("`youtube` has malformed attributes"),
+                "{line}"
+            );
+        }
+    }
+
+    #[test]
+    fn renders_leaf_and_container_directives() {
+        let html = render_markdown(
+            "Intro\n\n::youtube{id=abc}\n\n:::details{summary=\"More\"}\n\nHidden\n\n:::details\n\nNested\n\n:::\n\n:::\n\nOutro\n",
+        )
+        .unwrap();
+        assert_eq!(
+            html,
+            "<p>Intro</p>\n\
+             <div class=\"embed embed-youtube\"><iframe src=\"https://www.youtube-nocookie.com/embed/abc\" title=\"YouTube video\" loading=\"lazy\" allow=\"encrypted-media; picture-in-picture\" allowfullscreen></iframe></div>\
+             <details><summary>More</summary><p>Hidden</p>\n\
+             <details><summary>Details</summary><p>Nested</p>\n</details></details>\
+             <p>Outro</p>\n"
+        );
+    }
+
+    #[test]
+    fn figures_are_rendered_like_captioned_images() {
+        assert_eq!(
+            render_markdown("::figure{src=cat.png caption=\"A cat\"}\n").unwrap(),
+            render_markdown("![A cat](cat.png)\\\nA cat\n").unwrap()
+        );
+    }
+
+    #[test]
+    fn unknown_directives_are_errors() {
+        assert_eq!(
+            render_markdown("Intro\n\n::vimeo{id=abc}\n").unwrap_err(),
+            invalid("unknown directive `vimeo`", 3, 1)
+        );
+        // the unknown container must not close the known one around it
+        assert_eq!(
+            render_markdown(":::details\n\n:::spoiler\n\nHidden\n\n:::\n\n:::\n").unwrap_err(),
+            invalid("unknown directive `spoiler`", 3, 1)
+        );
+    }
+
+    #[test]
+    fn malformed_directives_are_errors_at_their_position() {
+        let cases = [
+            (
+                "Intro\n\n::youtube{id=\"abc}\n",
+                invalid("`youtube` has malformed attributes", 3, 1),
+            ),
+            (
+                "::youtube{title=Video}\n",
+                invalid("`::youtube` is missing the `id` attribute", 1, 1),
+            ),
+            (
+                "::youtube{id=abc}\nText right below\n",
+                invalid(
+                    "directives have to be separated from the surrounding text by blank lines",
+                    1,
+                    1,
+                ),
+            ),
+            (
+                "Intro\n\n:::details\n\nNever closed\n",
+                invalid("`:::details` is never closed", 3, 1),
+            ),
+            (
+                "Intro\n\n:::\n",
+                invalid("closing `:::` without an opening directive", 3, 1),
+            ),
+            (
+                "Intro\n\n::figure{src=cat.png}\n",
+                invalid("`::figure` is missing the `caption` attribute", 3, 1),
+            ),
+            (
+                "::details\n",
+                invalid("`details` has to be a `:::` container directive", 1, 1),
+            ),
+        ];
+        for (source, error) in cases {
+            assert_eq!(render_markdown(source).unwrap_err(), error, "{source}");
+        }
+    }
+}
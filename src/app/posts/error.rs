@@ -0,0 +1,93 @@
+use super::super::ErrorPage;
+use super::id::PostId;
+use super::load::{PostLoadError, similar_posts};
+use leptos::prelude::*;
+
+impl PostLoadError {
+    fn heading(&self) -> &'static str {
+        match self {
+            Self::InvalidId(_) => "Bad Request",
+            Self::NotFound => "Not Found",
+            _ => "Server Error",
+        }
+    }
+
+    fn friendly_message(&self) -> &'static str {
+        match self {
+            Self::InvalidId(_) => "That doesn't look like a link to a post.",
+            Self::NotFound => "This post doesn't exist (yet!)",
+            Self::NoMetadata | Self::MetadataParseFailed(_) | Self::AuthorsLoadFailed(_) => {
+                "This post is missing some of its details, so it can't be shown right now."
+            }
+            Self::MarkdownParseFailed(_)
+            | Self::SyntaxHighlightFailed(_)
+            | Self::RenderMathFailed(_)
+            | Self::FootnoteDefNotReferenced(_)
+            | Self::MultipleFootnoteDefinitions(_)
+            | Self::ImageProcessingFailed(_)
+            | Self::InvalidDirective { .. } => {
+                "Something in this post couldn't be rendered. It will be fixed soon."
+            }
+            Self::ServerFn(_) => "Something went wrong while loading this post. Try again later.",
+        }
+    }
+}
+
+/// Error page shown in place of a post that failed to load
+#[component]
+pub fn PostError(error: PostLoadError, post_id: Option<PostId>) -> impl IntoView {
+    let suggestions = match (&error, post_id) {
+        (PostLoadError::NotFound, Some(post_id)) => Some(view! { <SimilarPosts post_id /> }),
+        _ => None,
+    };
+    // internal error details are only useful while writing posts
+    let details = cfg!(debug_assertions).then(|| {
+        view! {
+            <details>
+                <summary>"Details"</summary>
+                <code>{error.to_string()}</code>
+            </details>
+        }
+    });
+
+    view! {
+        <ErrorPage heading=error.heading()>
+            <p>{error.friendly_message()}</p>
+            {suggestions}
+            {details}
+        </ErrorPage>
+    }
+}
+
+#[component]
+fn SimilarPosts(post_id: PostId) -> impl IntoView {
+    let similar = Resource::new(move || post_id.clone(), similar_posts);
+
+    view! {
+        <Suspense>
+            {move || {
+                similar
+                    .get()
+                    .and_then(Result::ok)
+                    .filter(|ids| !ids.is_empty())
+                    .map(|ids| {
+                        view! {
+                            <p>"Maybe you were looking for:"</p>
+                            <ul>
+                                {ids
+                                    .into_iter()
+                                    .map(|id| {
+                                        view! {
+                                            <li>
+                                                <a href=format!("/posts/{id}")>{id.to_string()}</a>
+                                            </li>
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()}
+                            </ul>
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
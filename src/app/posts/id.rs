@@ -45,7 +45,6 @@ pub enum PostIdError {
     ParseNumberFailed,
     InvalidFormat,
     Missing,
-    Unknown,
 }
 impl std::error::Error for PostIdError {}
 impl std::fmt::Display for PostIdError {
@@ -54,18 +53,6 @@ impl std::fmt::Display for PostIdError {
             PostIdError::ParseNumberFailed => write!(f, "parsing number failed"),
             PostIdError::InvalidFormat => write!(f, "invalid format (should be <number>[-<slug>])"),
             PostIdError::Missing => write!(f, "missing parameter"),
-            Self::Unknown => write!(f, "unk"),
-        }
-    }
-}
-impl std::str::FromStr for PostIdError {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "parsing number failed" => Ok(Self::ParseNumberFailed),
-            "invalid format (should be <number>[-<slug>])" => Ok(Self::InvalidFormat),
-            _ => Ok(Self::Unknown),
         }
     }
 }
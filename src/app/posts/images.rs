@@ -0,0 +1,231 @@
+//! Responsive variants of the images used in posts
+//!
+//! Resized AVIF and WebP copies of every local image are written to a cache
+//! directory outside of the site root, which can be read-only, and served from
+//! `/image-cache`. The cache mirrors the directories of the originals. A tiny blurry copy is
+//! inlined as the background of each image, and shown until the image itself
+//! is loaded. Images are only decoded when some of their copies are missing.
+
+use super::load::PostLoadError;
+use base64::Engine as _;
+use html_escape::encode_double_quoted_attribute as escape;
+use image::{DynamicImage, ImageFormat, imageops::FilterType};
+use std::cell::OnceCell;
+use std::path::{Component, Path, PathBuf};
+use std::sync::OnceLock;
+
+/// Directory holding the generated variants, see [`cache_dir`]
+static CACHE_DIR: OnceLock<PathBuf> = OnceLock::new();
+
+/// Path the cache directory is served from
+pub const CACHE_URL: &str = "/image-cache";
+
+/// Widths of the generated variants, images are never upscaled
+const VARIANT_WIDTHS: [u32; 4] = [480, 960, 1440, 1920];
+
+/// Longer side of the inlined placeholder, browsers blur it further when scaling it up
+const PLACEHOLDER_SIZE: u32 = 16;
+
+/// Value of the `sizes` attribute, matches the width of the post body
+const SIZES: &str = "(min-width: 1024px) 960px, 100vw";
+
+/// A local image referenced by a post
+pub struct PostImage {
+    /// URL of the original image, as written in the post
+    pub url: String,
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// decoded image, only when a copy has to be generated from it
+    image: OnceCell<DynamicImage>,
+}
+
+impl PostImage {
+    /// Opens an image served from `/assets`, `None` for images hosted elsewhere
+    pub fn open(url: &str, site_root: &str) -> Result<Option<Self>, PostLoadError> {
+        let Some(relative) = url.strip_prefix("/assets/") else {
+            return Ok(None);
+        };
+        let relative = Path::new(relative);
+        if !relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Err(PostLoadError::ImageProcessingFailed(format!(
+                "{url} points outside of the assets directory"
+            )));
+        }
+
+        let path = Path::new(site_root).join(relative);
+        let (width, height) = image::image_dimensions(&path)
+            .map_err(|e| PostLoadError::ImageProcessingFailed(format!("{url}: {e}")))?;
+
+        Ok(Some(Self {
+            url: url.to_string(),
+            path,
+            width,
+            height,
+            image: OnceCell::new(),
+        }))
+    }
+
+    /// The image itself, decoded the first time it's needed
+    fn image(&self) -> Result<&DynamicImage, PostLoadError> {
+        if let Some(image) = self.image.get() {
+            return Ok(image);
+        }
+        let image = image::open(&self.path)
+            .map_err(|e| PostLoadError::ImageProcessingFailed(format!("{}: {e}", self.url)))?;
+        Ok(self.image.get_or_init(|| image))
+    }
+
+    /// Generates the missing variants and renders a `<picture>` element using them
+    pub fn render(&self, alt: &str, title: Option<&str>) -> Result<String, PostLoadError> {
+        let avif_srcset = self.srcset(ImageFormat::Avif)?;
+        let webp_srcset = self.srcset(ImageFormat::WebP)?;
+        let placeholder = self.placeholder()?;
+
+        let alt = escape(alt);
+        let title = title
+            .map(|title| format!(" title=\"{}\"", escape(title)))
+            .unwrap_or_default();
+        Ok(format!(
+            "<picture><source type=\"image/avif\" srcset=\"{avif_srcset}\" sizes=\"{SIZES}\" /><source type=\"image/webp\" srcset=\"{webp_srcset}\" sizes=\"{SIZES}\" /><img src=\"{}\" alt=\"{alt}\"{title} width=\"{}\" height=\"{}\" class=\"placeholder\" style=\"background-image: url({placeholder})\" loading=\"lazy\" decoding=\"async\" /></picture>",
+            self.url, self.width, self.height
+        ))
+    }
+
+    /// Blurred thumbnail of the image as a `data:` URL
+    fn placeholder(&self) -> Result<String, PostLoadError> {
+        let err = |e: &dyn std::fmt::Display| {
+            PostLoadError::ImageProcessingFailed(format!("{}: {e}", self.url))
+        };
+
+        let path = cache_dir().join(self.cache_file_name("placeholder.png"));
+        if !is_up_to_date(&path, &self.path) {
+            let mut png = std::io::Cursor::new(Vec::new());
+            self.image()?
+                .thumbnail(PLACEHOLDER_SIZE, PLACEHOLDER_SIZE)
+                .blur(0.5)
+                .write_to(&mut png, ImageFormat::Png)
+                .map_err(|e| err(&e))?;
+            if let Some(dir) = path.parent() {
+                std::fs::create_dir_all(dir).map_err(|e| err(&e))?;
+            }
+            std::fs::write(&path, png.into_inner()).map_err(|e| err(&e))?;
+        }
+        let png = std::fs::read(&path).map_err(|e| err(&e))?;
+        Ok(format!(
+            "data:image/png;base64,{}",
+            base64::engine::general_purpose::STANDARD.encode(png)
+        ))
+    }
+
+    fn srcset(&self, format: ImageFormat) -> Result<String, PostLoadError> {
+        let largest = VARIANT_WIDTHS[VARIANT_WIDTHS.len() - 1];
+        let widths = VARIANT_WIDTHS
+            .into_iter()
+            .filter(|w| *w < self.width)
+            .chain((self.width <= largest).then_some(self.width));
+
+        let mut srcset = Vec::new();
+        for width in widths {
+            let extension = format.extensions_str()[0];
+            let file_name = self.cache_file_name(&format!("{width}w.{extension}"));
+            self.write_variant(&cache_dir().join(&file_name), width, format)?;
+            srcset.push(format!("{CACHE_URL}/{file_name} {width}w"));
+        }
+        Ok(srcset.join(", "))
+    }
+
+    /// Path of a generated copy relative to the cache directory, in the same
+    /// directories as the original and named after all of its file name, so
+    /// `photos/cat.jpg` becomes `photos/cat.jpg-<suffix>`
+    fn cache_file_name(&self, suffix: &str) -> String {
+        let name = self.url.trim_start_matches("/assets/");
+        format!("{name}-{suffix}")
+    }
+
+    fn write_variant(
+        &self,
+        path: &Path,
+        width: u32,
+        format: ImageFormat,
+    ) -> Result<(), PostLoadError> {
+        let err = |e: &dyn std::fmt::Display| {
+            PostLoadError::ImageProcessingFailed(format!("{}: {e}", path.display()))
+        };
+
+        if is_up_to_date(path, &self.path) {
+            return Ok(());
+        }
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| err(&e))?;
+        }
+
+        let height = (u64::from(self.height) * u64::from(width) / u64::from(self.width)).max(1);
+        let resized = self
+            .image()?
+            .resize_exact(width, height as u32, FilterType::Lanczos3)
+            .to_rgba8();
+
+        let encoded = match format {
+            ImageFormat::Avif => {
+                let mut avif = Vec::new();
+                DynamicImage::ImageRgba8(resized)
+                    .write_with_encoder(image::codecs::avif::AvifEncoder::new_with_speed_quality(
+                        &mut avif, 8, 75,
+                    ))
+                    .map_err(|e| err(&e))?;
+                avif
+            }
+            // the encoder of the `image` crate can only do lossless WebP,
+            // which ends up larger than the originals
+            _ => webp::Encoder::from_rgba(&resized, width, resized.height())
+                .encode(80.0)
+                .to_vec(),
+        };
+        std::fs::write(path, encoded).map_err(|e| err(&e))
+    }
+}
+
+/// Directory holding the generated images, in the temporary directory unless
+/// it's set with [`set_cache_dir`]
+pub fn cache_dir() -> &'static Path {
+    CACHE_DIR.get_or_init(|| std::env::temp_dir().join("personal-site-image-cache"))
+}
+
+/// Sets the directory holding the generated images, calls after the first
+/// image was generated are ignored
+pub fn set_cache_dir(dir: PathBuf) {
+    let _ = CACHE_DIR.set(dir);
+}
+
+/// Renders an image node, using responsive variants for images served from `/assets`
+pub fn render_image(
+    image: &markdown::mdast::Image,
+    site_root: &str,
+) -> Result<String, PostLoadError> {
+    if let Some(post_image) = PostImage::open(&image.url, site_root)? {
+        return post_image.render(&image.alt, image.title.as_deref());
+    }
+    let title = image
+        .title
+        .as_deref()
+        .map(|title| format!(" title=\"{}\"", escape(title)))
+        .unwrap_or_default();
+    Ok(format!(
+        "<img src=\"{}\" alt=\"{}\"{title} loading=\"lazy\" decoding=\"async\" />",
+        escape(&image.url),
+        escape(&image.alt)
+    ))
+}
+
+/// Checks if a generated file is newer than the file it was generated from
+pub fn is_up_to_date(generated: &Path, source: &Path) -> bool {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(generated), modified(source)) {
+        (Some(generated), Some(source)) => generated >= source,
+        _ => false,
+    }
+}
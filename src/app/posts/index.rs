@@ -0,0 +1,121 @@
+//! Index of the published posts
+//!
+//! Listing posts reads the front matter of every post file, and finding related
+//! posts compares every post to every other one, so both are done once when the
+//! index is built. So is looking up when posts were last updated. The index is
+//! kept until the posts are reloaded, except in debug builds, which build it
+//! again when a post file changes.
+
+use super::id::PostId;
+use super::load::{
+    PostLoadError, PostMetadata, last_updated, list_post_ids, read_metadata, read_post_source,
+};
+use super::navigation::PostLink;
+use super::related::find_related;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::SystemTime;
+
+pub struct PostIndex {
+    /// metadata of every published post, newest first
+    pub posts: Vec<(PostId, PostMetadata)>,
+    /// most related posts of every post, by post number
+    pub related: HashMap<usize, Vec<PostLink>>,
+    /// date of the last commit changing every post, drafts included, by post number,
+    /// only if it's after the post was published
+    pub updated: HashMap<usize, chrono::NaiveDate>,
+    /// posts left out because their metadata can't be read, with their errors
+    pub failed: Vec<(PostId, PostLoadError)>,
+    /// when the index was built, pages listing other posts are stale before it
+    pub built: SystemTime,
+    /// newest modification time in the posts directory when the index was built
+    modified: Option<SystemTime>,
+}
+
+static INDEX: RwLock<Option<Arc<PostIndex>>> = RwLock::new(None);
+
+/// The post index, built when it's first needed
+pub fn post_index() -> Result<Arc<PostIndex>, PostLoadError> {
+    if let Some(index) = INDEX.read().unwrap().as_ref()
+        && (!cfg!(debug_assertions) || index.modified == posts_modified())
+    {
+        return Ok(index.clone());
+    }
+    rebuild_post_index()
+}
+
+/// Builds the post index from the post files, replacing the current one when it's done
+pub fn rebuild_post_index() -> Result<Arc<PostIndex>, PostLoadError> {
+    let index = Arc::new(build_post_index()?);
+    *INDEX.write().unwrap() = Some(index.clone());
+    Ok(index)
+}
+
+/// Metadata of every published post, newest first
+///
+/// Posts without readable metadata are left out, so one broken post doesn't
+/// break every listing. Drafts are left out too, they can only be opened by their URL.
+pub fn list_posts() -> Result<Vec<(PostId, PostMetadata)>, PostLoadError> {
+    Ok(post_index()?.posts.clone())
+}
+
+/// Posts left out of the index, with their errors
+///
+/// Listings leave these out, so nothing else notices them.
+pub fn failing_posts() -> Result<Vec<(PostId, PostLoadError)>, PostLoadError> {
+    Ok(post_index()?.failed.clone())
+}
+
+/// Reads the front matter of every post and when they were last committed,
+/// nothing is rendered
+fn build_post_index() -> Result<PostIndex, PostLoadError> {
+    let modified = posts_modified();
+
+    let mut posts = Vec::new();
+    let mut updated = HashMap::new();
+    let mut failed = Vec::new();
+    for post_id in list_post_ids()? {
+        match read_post_source(&post_id).and_then(|post_raw| read_metadata(&post_raw)) {
+            Ok(mut metadata) => {
+                let date = metadata.date;
+                if let Some(committed) = last_updated(&post_id).filter(|updated| *updated > date) {
+                    updated.insert(post_id.number, committed);
+                }
+                metadata.updated = metadata
+                    .updated
+                    .or(updated.get(&post_id.number).copied())
+                    .filter(|updated| *updated > date);
+                if !metadata.draft {
+                    posts.push((post_id, metadata));
+                }
+            }
+            Err(e) => {
+                tracing::warn!(%post_id, "{e}");
+                failed.push((post_id, e));
+            }
+        }
+    }
+    posts.sort_by_key(|(_, metadata)| std::cmp::Reverse(metadata.date));
+
+    Ok(PostIndex {
+        related: find_related(&posts),
+        posts,
+        updated,
+        failed,
+        built: SystemTime::now(),
+        modified,
+    })
+}
+
+/// Newest modification time of the posts directory and the files in it
+fn posts_modified() -> Option<SystemTime> {
+    let config = leptos::config::get_configuration(None).unwrap();
+    let posts = format!("{}/posts", config.leptos_options.site_root);
+    let directory = std::fs::metadata(&posts).and_then(|m| m.modified()).ok();
+    std::fs::read_dir(&posts)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .chain(directory)
+        .max()
+}
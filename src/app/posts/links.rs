@@ -0,0 +1,147 @@
+//! Links in posts
+//!
+//! Internal links are checked offline against the posts directory, the ids in
+//! the rendered post and the files served from `/assets`. External links are
+//! never requested. Checking takes every post, so it's done by `--check-links`
+//! instead of every time a post is rendered.
+
+use super::id::PostId;
+use markdown::mdast::{Definition, Link, Node};
+use std::collections::{HashMap, HashSet};
+use std::path::{Component, Path};
+use std::str::FromStr as _;
+use std::sync::LazyLock;
+
+/// Checks if a link points to another site
+pub fn is_external_url(url: &str) -> bool {
+    ["http://", "https://", "//"]
+        .iter()
+        .any(|prefix| url.starts_with(prefix))
+}
+
+/// Replaces `[text][label]` references with the links they are defined as,
+/// so they are handled like every other link, and removes links nested in links
+pub fn normalize_links(root: &mut Node) {
+    let mut definitions = HashMap::new();
+    collect_definitions(root, &mut definitions);
+    replace_link_references(root, &definitions);
+    unwrap_nested_links(root, false);
+}
+
+fn collect_definitions(node: &Node, definitions: &mut HashMap<String, Definition>) {
+    if let Node::Definition(definition) = node {
+        // the first definition of a label wins, like in CommonMark
+        definitions
+            .entry(definition.identifier.clone())
+            .or_insert_with(|| definition.clone());
+    }
+    for child in node.children().into_iter().flatten() {
+        collect_definitions(child, definitions);
+    }
+}
+
+fn replace_link_references(node: &mut Node, definitions: &HashMap<String, Definition>) {
+    if let Node::LinkReference(reference) = node
+        && let Some(definition) = definitions.get(&reference.identifier)
+    {
+        *node = Node::Link(Link {
+            children: std::mem::take(&mut reference.children),
+            position: reference.position.take(),
+            url: definition.url.clone(),
+            title: definition.title.clone(),
+        });
+    }
+    for child in node.children_mut().into_iter().flatten() {
+        replace_link_references(child, definitions);
+    }
+}
+
+/// Replaces links inside other links with their text, e.g. a URL autolinked in
+/// the text of `[https://example.com](https://example.com)`
+fn unwrap_nested_links(node: &mut Node, in_link: bool) {
+    let in_link = in_link || matches!(node, Node::Link(_));
+    let Some(children) = node.children_mut() else {
+        return;
+    };
+    for child in children.iter_mut() {
+        unwrap_nested_links(child, in_link);
+    }
+    if in_link {
+        *children = std::mem::take(children)
+            .into_iter()
+            .flat_map(|child| match child {
+                Node::Link(link) => link.children,
+                child => vec![child],
+            })
+            .collect();
+    }
+}
+
+/// A link in a post pointing to something that doesn't exist
+pub struct BrokenLink {
+    pub url: String,
+    pub reason: &'static str,
+}
+impl std::fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "broken link `{}`: {}", self.url, self.reason)
+    }
+}
+
+static ID_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"\sid="([^"]*)""#).unwrap());
+
+static LINK_RE: LazyLock<regex::Regex> =
+    LazyLock::new(|| regex::Regex::new(r#"\s(?:href|src)="([^"]*)""#).unwrap());
+
+/// Finds the internal links of a rendered post that point nowhere
+pub fn check_links(html: &str, site_root: &str, post_ids: &[PostId]) -> Vec<BrokenLink> {
+    let ids: HashSet<_> = ID_RE
+        .captures_iter(html)
+        .map(|c| html_escape::decode_html_entities(&c[1]).to_string())
+        .collect();
+
+    LINK_RE
+        .captures_iter(html)
+        .map(|c| html_escape::decode_html_entities(&c[1]).to_string())
+        .filter_map(|url| {
+            let reason = check_link(&url, site_root, post_ids, &ids)?;
+            Some(BrokenLink { url, reason })
+        })
+        .collect()
+}
+
+/// Reason why a link is broken, `None` for working and unchecked links
+fn check_link(
+    url: &str,
+    site_root: &str,
+    post_ids: &[PostId],
+    ids: &HashSet<String>,
+) -> Option<&'static str> {
+    if let Some(anchor) = url.strip_prefix('#') {
+        return (!ids.contains(anchor)).then_some("no heading or element with this id");
+    }
+
+    let path = url.split(['#', '?']).next().unwrap_or_default();
+    if let Some(id) = path.strip_prefix("/posts/") {
+        if id.is_empty() {
+            return None;
+        }
+        return match PostId::from_str(id) {
+            Ok(id) if post_ids.contains(&id) => None,
+            Ok(_) => Some("post doesn't exist"),
+            Err(_) => Some("invalid post ID"),
+        };
+    }
+    if let Some(asset) = path.strip_prefix("/assets/") {
+        let asset = Path::new(asset);
+        if !asset
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+        {
+            return Some("points outside of the assets directory");
+        }
+        return (!Path::new(site_root).join(asset).is_file()).then_some("asset doesn't exist");
+    }
+    None
+}
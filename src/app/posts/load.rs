@@ -1,8 +1,27 @@
+use super::super::authors::Author;
+#[cfg(feature = "ssr")]
+use super::super::authors::resolve_authors;
+#[cfg(feature = "ssr")]
+use super::directive::render_with_directives;
 use super::id::{PostId, PostIdError};
+#[cfg(feature = "ssr")]
+use super::images::render_image;
+#[cfg(feature = "ssr")]
+use super::links::{BrokenLink, check_links, is_external_url, normalize_links};
+#[cfg(feature = "ssr")]
+use super::render::{Hooks, render, render_children, to_text};
+#[cfg(feature = "ssr")]
+use super::sanitize::sanitize;
 use leptos::prelude::*;
+use leptos::server_fn::{
+    codec::JsonEncoding,
+    error::{FromServerFnError, ServerFnErrorErr},
+};
+#[cfg(feature = "ssr")]
+use markdown::mdast::Node;
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum PostLoadError {
     InvalidId(PostIdError),
     MarkdownParseFailed(String),
@@ -13,7 +32,14 @@ pub enum PostLoadError {
     MetadataParseFailed(String),
     FootnoteDefNotReferenced(String),
     MultipleFootnoteDefinitions(String),
-    Unknown,
+    ImageProcessingFailed(String),
+    AuthorsLoadFailed(String),
+    InvalidDirective {
+        reason: String,
+        line: usize,
+        column: usize,
+    },
+    ServerFn(ServerFnErrorErr),
 }
 impl std::fmt::Display for PostLoadError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
@@ -31,42 +57,72 @@ impl std::fmt::Display for PostLoadError {
             Self::MultipleFootnoteDefinitions(s) => {
                 write!(f, "Multiple footnote definitions present for {s}")
             }
-            Self::Unknown => write!(f, "Unknown error"),
+            Self::ImageProcessingFailed(s) => write!(f, "Processing image failed: {s}"),
+            Self::AuthorsLoadFailed(s) => write!(f, "Loading post authors failed: {s}"),
+            Self::InvalidDirective {
+                reason,
+                line,
+                column,
+            } => write!(f, "Invalid directive at {line}:{column}: {reason}"),
+            Self::ServerFn(e) => write!(f, "Server function failed: {e}"),
         }
     }
 }
 impl std::error::Error for PostLoadError {}
-impl std::str::FromStr for PostLoadError {
-    type Err = ();
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        if let Some(s) = s.strip_prefix("Invalid post ID: ") {
-            return Ok(Self::InvalidId(PostIdError::from_str(s)?));
-        }
-        if let Some(s) = s.strip_prefix("Parsing markdown failed: ") {
-            return Ok(Self::MarkdownParseFailed(String::from(s)));
-        }
-        if let Some(s) = s.strip_prefix("Code syntax highlighting failed: ") {
-            return Ok(Self::SyntaxHighlightFailed(String::from(s)));
-        }
-        if let Some(s) = s.strip_prefix("Rendering math failed: ") {
-            return Ok(Self::RenderMathFailed(String::from(s)));
-        }
-        if let Some(s) = s.strip_prefix("Post metadata parsing failed: ") {
-            return Ok(Self::MetadataParseFailed(String::from(s)));
-        }
-        if let Some(s) = s.strip_prefix("No references to footnote definition: ") {
-            return Ok(Self::FootnoteDefNotReferenced(String::from(s)));
-        }
-        if let Some(s) = s.strip_prefix("Multiple footnote definitions present for ") {
-            return Ok(Self::MultipleFootnoteDefinitions(String::from(s)));
-        }
-        match s {
-            "Post doesn't exist (yet!)" => Ok(Self::NotFound),
-            "Post file has no metadata" => Ok(Self::NoMetadata),
-            _ => Ok(Self::Unknown),
+impl PostLoadError {
+    /// HTTP status code to respond with when loading a post fails
+    #[cfg(feature = "ssr")]
+    pub fn status_code(&self) -> actix_web::http::StatusCode {
+        use actix_web::http::StatusCode;
+        match self {
+            Self::InvalidId(_) => StatusCode::BAD_REQUEST,
+            Self::NotFound => StatusCode::NOT_FOUND,
+            Self::MarkdownParseFailed(_)
+            | Self::SyntaxHighlightFailed(_)
+            | Self::RenderMathFailed(_)
+            | Self::NoMetadata
+            | Self::MetadataParseFailed(_)
+            | Self::FootnoteDefNotReferenced(_)
+            | Self::MultipleFootnoteDefinitions(_)
+            | Self::ImageProcessingFailed(_)
+            | Self::AuthorsLoadFailed(_)
+            | Self::InvalidDirective { .. } => StatusCode::INTERNAL_SERVER_ERROR,
+            Self::ServerFn(ServerFnErrorErr::Args(_) | ServerFnErrorErr::MissingArg(_)) => {
+                StatusCode::BAD_REQUEST
+            }
+            Self::ServerFn(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
+
+    /// Counts the error in the `post_load_errors_total` metric, labeled by its variant
+    #[cfg(feature = "ssr")]
+    pub fn count(&self) {
+        let kind = match self {
+            Self::InvalidId(_) => "invalid_id",
+            Self::MarkdownParseFailed(_) => "markdown_parse_failed",
+            Self::SyntaxHighlightFailed(_) => "syntax_highlight_failed",
+            Self::RenderMathFailed(_) => "render_math_failed",
+            Self::NotFound => "not_found",
+            Self::NoMetadata => "no_metadata",
+            Self::MetadataParseFailed(_) => "metadata_parse_failed",
+            Self::FootnoteDefNotReferenced(_) => "footnote_def_not_referenced",
+            Self::MultipleFootnoteDefinitions(_) => "multiple_footnote_definitions",
+            Self::ImageProcessingFailed(_) => "image_processing_failed",
+            Self::AuthorsLoadFailed(_) => "authors_load_failed",
+            Self::InvalidDirective { .. } => "invalid_directive",
+            Self::ServerFn(_) => "server_fn",
+        };
+        metrics::counter!("post_load_errors_total", "kind" => kind).increment(1);
+    }
+}
+impl FromServerFnError for PostLoadError {
+    // errors are sent to the client as JSON through the serde derives,
+    // so every variant arrives with all of its fields intact
+    type Encoder = JsonEncoding;
+
+    fn from_server_fn_error(value: ServerFnErrorErr) -> Self {
+        Self::ServerFn(value)
+    }
 }
 
 #[cfg(feature = "ssr")]
@@ -83,124 +139,461 @@ struct CachedPost {
     last_update: std::time::Instant,
 }
 
+#[cfg(feature = "ssr")]
+static POST_CACHE: std::sync::OnceLock<std::sync::Arc<std::sync::Mutex<PostCache>>> =
+    std::sync::OnceLock::new();
+
+/// Renders the given posts into a new post cache, which replaces the current
+/// one when every post is done, so posts keep being served from the cache meanwhile
+///
+/// Posts failing to render keep their current cached version, posts not given are dropped.
+#[cfg(feature = "ssr")]
+pub fn refresh_post_cache(post_ids: impl IntoIterator<Item = PostId>) {
+    use std::time::Instant;
+
+    let mut fresh = PostCache::default();
+    let mut failed = Vec::new();
+    for post_id in post_ids {
+        match render_post_file(&post_id) {
+            Ok(post) => {
+                fresh.entries.insert(
+                    post_id.number,
+                    CachedPost {
+                        content: post,
+                        last_update: Instant::now(),
+                    },
+                );
+                fresh.ids.insert(post_id.number, post_id);
+            }
+            Err(e) => {
+                e.count();
+                tracing::warn!(%post_id, "{e}");
+                failed.push(post_id);
+            }
+        }
+    }
+
+    let mut post_cache = POST_CACHE.get_or_init(Default::default).lock().unwrap();
+    for post_id in failed {
+        if let Some(post) = post_cache.entries.remove(&post_id.number) {
+            fresh.entries.insert(post_id.number, post);
+            fresh.ids.insert(post_id.number, post_id);
+        }
+    }
+    metrics::counter!("post_cache_evictions_total").increment(post_cache.entries.len() as u64);
+    *post_cache = fresh;
+}
+
 #[server]
-pub async fn load_post_content(post_id: PostId) -> Result<Post, ServerFnError<PostLoadError>> {
-    use std::io::ErrorKind;
-    use std::sync::{Arc, Mutex, OnceLock};
-    use std::time::{Duration, Instant};
-
-    static POST_CACHE: OnceLock<Arc<Mutex<PostCache>>> = OnceLock::new();
-    let md_options = markdown::Options {
-        parse: markdown::ParseOptions {
-            constructs: markdown::Constructs {
-                math_flow: true,
-                math_text: true,
-                frontmatter: true,
-                ..markdown::Constructs::gfm()
-            },
-            ..Default::default()
-        },
-        compile: markdown::CompileOptions {
-            allow_any_img_src: true,
-            // emitted html is controlled by us here
-            // so this option can be considered somewhat more safe (:D)
-            allow_dangerous_html: true,
-            allow_dangerous_protocol: true,
-            ..Default::default()
-        },
+pub async fn load_post_content(post_id: PostId) -> Result<Post, PostLoadError> {
+    let result = load_post(post_id.clone());
+    let resp = expect_context::<leptos_actix::ResponseOptions>();
+    match &result {
+        Ok(_) => set_cache_headers(&resp, &post_id),
+        Err(e) => resp.set_status(e.status_code()),
+    }
+    result
+}
+
+/// Sets `ETag` and `Last-Modified` of a post page from the post source, so
+/// browsers can revalidate the page instead of downloading it again
+///
+/// The page also shows its authors and links to other posts, so a change to
+/// `authors.toml` or a new build of the post index makes every page stale.
+#[cfg(feature = "ssr")]
+fn set_cache_headers(resp: &leptos_actix::ResponseOptions, post_id: &PostId) {
+    use actix_web::http::header::{self, HeaderValue, HttpDate};
+    use std::hash::{DefaultHasher, Hash, Hasher};
+
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = &config.leptos_options.site_root;
+    let Ok(source) = std::fs::metadata(format!("{site_root}/posts/{post_id}.md")) else {
+        return;
+    };
+    let Ok(index) = super::index::post_index() else {
+        return;
+    };
+    let authors = std::fs::metadata(format!("{site_root}/authors.toml"))
+        .and_then(|m| m.modified())
+        .ok();
+    let Some(last_modified) = std::fs::read_dir(format!("{site_root}/posts"))
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok()?.metadata().ok()?.modified().ok())
+        .chain(authors)
+        .chain([index.built])
+        .max()
+    else {
+        return;
     };
 
-    let mut post_cache = POST_CACHE
-        .get_or_init(|| Default::default())
-        .lock()
-        .unwrap();
-    eprintln!(
-        "cached: {},{}",
-        post_cache.ids.len(),
-        post_cache.entries.len()
+    let mut hasher = DefaultHasher::new();
+    (
+        source.len(),
+        source.modified().ok(),
+        authors,
+        index.built,
+        last_modified,
+    )
+        .hash(&mut hasher);
+    // the markup of the page changes with the site itself too
+    env!("CARGO_PKG_VERSION").hash(&mut hasher);
+    let etag = format!("W/\"{:x}\"", hasher.finish());
+
+    resp.insert_header(header::ETAG, HeaderValue::from_str(&etag).unwrap());
+    resp.insert_header(
+        header::LAST_MODIFIED,
+        HeaderValue::from_str(&HttpDate::from(last_modified).to_string()).unwrap(),
     );
+    resp.insert_header(header::CACHE_CONTROL, HeaderValue::from_static("no-cache"));
+}
+
+#[cfg(feature = "ssr")]
+#[tracing::instrument(level = "debug", skip_all, fields(%post_id, cache = tracing::field::Empty))]
+pub fn load_post(post_id: PostId) -> Result<Post, PostLoadError> {
+    use std::time::Instant;
 
-    #[cfg(not(debug_assertions))]
-    if let Some(post) = post_cache.entries.get(&post_id.number) {
-        if post.last_update.elapsed() < Duration::from_secs(12 * 60 * 60) {
+    let post_cache = POST_CACHE.get_or_init(Default::default);
+    {
+        let post_cache = post_cache.lock().unwrap();
+        tracing::trace!(
+            ids = post_cache.ids.len(),
+            entries = post_cache.entries.len(),
+            "post cache"
+        );
+
+        #[cfg(not(debug_assertions))]
+        if let Some(post) = post_cache.entries.get(&post_id.number)
+            && post.last_update.elapsed() < std::time::Duration::from_secs(12 * 60 * 60)
+        {
+            tracing::Span::current().record("cache", "hit");
+            metrics::counter!("post_cache_hits_total").increment(1);
             return Ok(post.content.clone());
         }
     }
+    tracing::Span::current().record("cache", "miss");
+    metrics::counter!("post_cache_misses_total").increment(1);
 
-    if let Some(cached_id) = post_cache.ids.get(&post_id.number) {}
+    // rendering can take a while with new images, so the cache is only locked
+    // again to store the result
+    let result = render_post_file(&post_id).inspect(|post| {
+        let mut post_cache = post_cache.lock().unwrap();
+        post_cache.ids.insert(post_id.number, post_id.clone());
+        let replaced = post_cache.entries.insert(
+            post_id.number,
+            CachedPost {
+                content: post.clone(),
+                last_update: Instant::now(),
+            },
+        );
+        if replaced.is_some() {
+            metrics::counter!("post_cache_evictions_total").increment(1);
+        }
+    });
+    if let Err(e) = &result {
+        e.count();
+    }
+    result
+}
 
-    let resp = expect_context::<leptos_actix::ResponseOptions>();
+/// Reads and renders a post file, with its last updated date from the post index
+#[cfg(feature = "ssr")]
+fn render_post_file(post_id: &PostId) -> Result<Post, PostLoadError> {
     let config = leptos::config::get_configuration(None).unwrap();
     let site_root = &config.leptos_options.site_root;
 
-    let post_path = format!("{}/posts/{}-{}.md", site_root, post_id.number, post_id.slug);
-    eprintln!("{post_path}");
-    match std::fs::read_to_string(post_path) {
-        Ok(post_raw) => {
-            // safe to unwrap because markdown doesn't have syntax errors
-            let mdast = markdown::to_mdast(&post_raw, &md_options.parse).unwrap();
-            let mut metadata = None;
-            let mut footnotes = std::collections::HashMap::new();
-            let Some(preprocessed_mdast) =
-                preprocess(mdast, &mut metadata, &mut footnotes, &md_options)
-                    .map_err(|e| ServerFnError::from(e))?
-            else {
-                return Err(PostLoadError::MarkdownParseFailed(
-                    "preprocess returned no root element".to_string(),
-                )
-                .into());
-            };
-            let Some(metadata) = metadata else {
-                return Err(PostLoadError::NoMetadata.into());
-            };
+    tracing::debug!(%post_id, "rendering post");
+    let mut post = render_post(&read_post_source(post_id)?, site_root)?;
+    let date = post.metadata.date;
+    post.metadata.updated = post
+        .metadata
+        .updated
+        .or_else(|| {
+            let index = super::index::post_index().ok()?;
+            index.updated.get(&post_id.number).copied()
+        })
+        .filter(|updated| *updated > date);
+    Ok(post)
+}
 
-            let preprocessed_md = mdast_util_to_markdown::to_markdown(&preprocessed_mdast)
-                .map_err(|e| {
-                    eprintln!("to_markdown() failed: {e:?}");
-                    PostLoadError::MarkdownParseFailed(e.reason)
-                })?;
-            let html =
-                markdown::to_html_with_options(&preprocessed_md, &md_options).map_err(|e| {
-                    eprintln!("to_html_with_options() failed: {e:?}");
-                    PostLoadError::MarkdownParseFailed(e.reason)
-                })?;
-
-            post_cache.ids.insert(post_id.number, post_id.clone());
-            let post = Post { html, metadata };
-            post_cache.entries.insert(
-                post_id.number,
-                CachedPost {
-                    content: post.clone(),
-                    last_update: Instant::now(),
-                },
-            );
-            Ok(post)
-        }
-        Err(e) => match e.kind() {
-            ErrorKind::NotFound => Err(PostLoadError::NotFound.into()),
-            _ => Err(ServerFnError::ServerError(
-                "failed to read post file".to_string(),
-            )),
+/// Runs a stage of rendering a post, recording how long it took in the
+/// `post_render_duration_seconds` metric
+#[cfg(feature = "ssr")]
+fn timed<T>(stage: &'static str, f: impl FnOnce() -> T) -> T {
+    let start = std::time::Instant::now();
+    let result = f();
+    metrics::histogram!("post_render_duration_seconds", "stage" => stage)
+        .record(start.elapsed().as_secs_f64());
+    result
+}
+
+/// Average reading speed used for the reading time of posts
+#[cfg(feature = "ssr")]
+const WORDS_PER_MINUTE: usize = 200;
+
+/// Renders the markdown source of a post to HTML
+#[cfg(feature = "ssr")]
+fn render_post(post_raw: &str, site_root: &str) -> Result<Post, PostLoadError> {
+    let parse_options = markdown::ParseOptions {
+        constructs: markdown::Constructs {
+            math_flow: true,
+            math_text: true,
+            frontmatter: true,
+            ..markdown::Constructs::gfm()
         },
+        ..Default::default()
+    };
+
+    // safe to unwrap because markdown doesn't have syntax errors
+    let mut mdast = tracing::debug_span!("parse_markdown").in_scope(|| {
+        timed("markdown", || {
+            markdown::to_mdast(post_raw, &parse_options).unwrap()
+        })
+    });
+    normalize_links(&mut mdast);
+
+    let mut renderer = PostRenderer {
+        source: post_raw,
+        site_root,
+        metadata: None,
+        footnotes: Default::default(),
+        figure_count: 0,
+        word_count: 0,
+    };
+    let html = render(&mdast, &mut renderer)?;
+    let Some(metadata) = renderer.metadata else {
+        return Err(PostLoadError::NoMetadata);
+    };
+    let authors = resolve_authors(&metadata.authors, site_root)
+        .map_err(|e| PostLoadError::AuthorsLoadFailed(e.to_string()))?;
+
+    // raw html written in posts is rendered as it is, so it has to be sanitized
+    Ok(Post {
+        html: sanitize(&html),
+        metadata,
+        authors,
+        word_count: renderer.word_count,
+        reading_time: renderer.word_count.div_ceil(WORDS_PER_MINUTE).max(1),
+    })
+}
+
+/// Where the post files are copied into the site root from, relative to the
+/// working directory of the server
+#[cfg(feature = "ssr")]
+const POSTS_SOURCE_DIR: &str = "assets/posts";
+
+/// Date of the last commit changing the source of a post
+///
+/// The copy of the post in the site root isn't committed, and its modification
+/// time is when the site was built, so this is `None` outside of a checkout of
+/// the site, e.g. in the container image.
+#[cfg(feature = "ssr")]
+pub fn last_updated(post_id: &PostId) -> Option<chrono::NaiveDate> {
+    let output = std::process::Command::new("git")
+        .args(["log", "-1", "--format=%cs", "--"])
+        .arg(format!("{post_id}.md"))
+        .current_dir(POSTS_SOURCE_DIR)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let date = String::from_utf8(output.stdout).ok()?;
+    chrono::NaiveDate::parse_from_str(date.trim(), "%Y-%m-%d").ok()
+}
+
+/// Checks the internal links of every post, without having to request them one by one
+#[cfg(feature = "ssr")]
+pub fn check_post_links() -> Result<Vec<(PostId, BrokenLink)>, PostLoadError> {
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = &config.leptos_options.site_root;
+    let post_ids = list_post_ids()?;
+
+    let mut broken_links = Vec::new();
+    for post_id in &post_ids {
+        let post_raw =
+            std::fs::read_to_string(format!("{site_root}/posts/{post_id}.md")).map_err(|_| {
+                PostLoadError::ServerFn(ServerFnErrorErr::ServerError(
+                    "failed to read post file".to_string(),
+                ))
+            })?;
+        let post = render_post(&post_raw, site_root)?;
+        broken_links.extend(
+            check_links(&post.html, site_root, &post_ids)
+                .into_iter()
+                .map(|broken_link| (post_id.clone(), broken_link)),
+        );
+    }
+    Ok(broken_links)
+}
+
+#[server]
+pub async fn similar_posts(post_id: PostId) -> Result<Vec<PostId>, PostLoadError> {
+    use std::collections::HashSet;
+
+    let slug_words = |id: &PostId| -> HashSet<String> {
+        id.slug
+            .split(['-', '_'])
+            .filter(|w| !w.is_empty())
+            .map(String::from)
+            .collect()
+    };
+    let requested_words = slug_words(&post_id);
+
+    // drafts aren't listed anywhere, so they aren't suggested either
+    let mut scored: Vec<(usize, PostId)> = super::index::list_posts()?
+        .into_iter()
+        .map(|(id, _)| {
+            let mut score = slug_words(&id).intersection(&requested_words).count();
+            if id.number == post_id.number {
+                score += 1;
+            }
+            (score, id)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    scored.sort_by(|(a, _), (b, _)| b.cmp(a));
+
+    Ok(scored.into_iter().take(3).map(|(_, id)| id).collect())
+}
+
+/// Markdown source of a post
+#[cfg(feature = "ssr")]
+pub fn read_post_source(post_id: &PostId) -> Result<String, PostLoadError> {
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = &config.leptos_options.site_root;
+
+    std::fs::read_to_string(format!("{site_root}/posts/{post_id}.md")).map_err(|e| match e.kind() {
+        std::io::ErrorKind::NotFound => PostLoadError::NotFound,
+        _ => PostLoadError::ServerFn(ServerFnErrorErr::ServerError(
+            "failed to read post file".to_string(),
+        )),
+    })
+}
+
+/// Syntax of the front matter of a post
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy)]
+enum FrontMatter {
+    /// between `---` lines
+    Yaml,
+    /// between `+++` lines
+    Toml,
+}
+
+/// Reads the metadata of a post from its front matter, without parsing the rest
+#[cfg(feature = "ssr")]
+pub fn read_metadata(post_raw: &str) -> Result<PostMetadata, PostLoadError> {
+    let mut lines = post_raw.lines();
+    let (syntax, fence) = match lines.next().map(str::trim_end) {
+        Some("---") => (FrontMatter::Yaml, "---"),
+        Some("+++") => (FrontMatter::Toml, "+++"),
+        _ => return Err(PostLoadError::NoMetadata),
+    };
+
+    let mut front_matter = String::new();
+    for line in lines {
+        if line.trim_end() == fence {
+            return parse_metadata(&front_matter, syntax);
+        }
+        front_matter.push_str(line);
+        front_matter.push('\n');
+    }
+    Err(PostLoadError::NoMetadata)
+}
+
+#[cfg(feature = "ssr")]
+fn parse_metadata(front_matter: &str, syntax: FrontMatter) -> Result<PostMetadata, PostLoadError> {
+    match syntax {
+        FrontMatter::Yaml => serde_yaml::from_str(front_matter)
+            .map_err(|e| PostLoadError::MetadataParseFailed(format!("{e:?}"))),
+        FrontMatter::Toml => toml::from_str(front_matter)
+            .map_err(|e| PostLoadError::MetadataParseFailed(format!("{e:?}"))),
     }
 }
 
+/// IDs of every post file in the `posts` directory of the site root
+#[cfg(feature = "ssr")]
+pub fn list_post_ids() -> Result<Vec<PostId>, PostLoadError> {
+    use std::str::FromStr as _;
+
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = &config.leptos_options.site_root;
+
+    let entries = std::fs::read_dir(format!("{site_root}/posts")).map_err(|_| {
+        PostLoadError::ServerFn(ServerFnErrorErr::ServerError(
+            "failed to read posts directory".to_string(),
+        ))
+    })?;
+
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| {
+            let file_name = entry.file_name();
+            let id = file_name.to_str()?.strip_suffix(".md")?;
+            PostId::from_str(id).ok()
+        })
+        .collect())
+}
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct PostMetadata {
     pub title: String,
     pub description: String,
     pub date: chrono::NaiveDate,
-    #[serde(default = "default_author")]
-    pub author: String,
+    /// date of the last significant change, if not given it's looked up when the post index is built,
+    /// dates before or on `date` are dropped
+    #[serde(default)]
+    pub updated: Option<chrono::NaiveDate>,
+    /// IDs of the authors in the authors registry, `author` works too for a single author
+    #[serde(
+        default = "default_authors",
+        alias = "author",
+        deserialize_with = "one_or_many"
+    )]
+    pub authors: Vec<String>,
+    #[serde(default)]
+    pub series: Option<PostSeries>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// image shown in link previews of the post
+    #[serde(default)]
+    pub image: Option<String>,
+    /// drafts are not listed anywhere, but can be opened by their URL
+    #[serde(default)]
+    pub draft: bool,
+}
+fn default_authors() -> Vec<String> {
+    vec!["akosnad".to_string()]
+}
+fn one_or_many<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(id) => vec![id],
+        OneOrMany::Many(ids) => ids,
+    })
 }
-fn default_author() -> String {
-    "akosnad".to_string()
+
+/// Series of posts a post is part of
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostSeries {
+    pub name: String,
+    /// position of the post in the series, parts are listed in ascending order
+    pub order: usize,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Post {
     pub html: String,
     pub metadata: PostMetadata,
+    /// profiles of the authors in the metadata
+    pub authors: Vec<Author>,
+    pub word_count: usize,
+    /// estimated time to read the post, in minutes
+    pub reading_time: usize,
 }
 
 #[cfg(feature = "ssr")]
@@ -211,203 +604,373 @@ struct Footnote {
     defined: bool,
 }
 
+/// Renders the nodes of a post which need more than what markdown can do
 #[cfg(feature = "ssr")]
-fn preprocess(
-    mut content: markdown::mdast::Node,
-    metadata: &mut Option<PostMetadata>,
-    footnotes: &mut std::collections::HashMap<String, Footnote>,
-    md_options: &markdown::Options,
-) -> Result<Option<markdown::mdast::Node>, PostLoadError> {
-    use markdown::mdast::{
-        Code, Delete, FootnoteDefinition, FootnoteReference, Heading, Html, InlineMath, Link, Math,
-        Node, Text, Toml, Yaml,
-    };
+struct PostRenderer<'a> {
+    source: &'a str,
+    site_root: &'a str,
+    metadata: Option<PostMetadata>,
+    footnotes: std::collections::HashMap<String, Footnote>,
+    figure_count: usize,
+    word_count: usize,
+}
 
-    if let Some(children) = content.children_mut() {
-        // preprocess children
-        let new_children: Result<Vec<Option<Node>>, _> = children
-            .iter()
-            .map(|c| preprocess(c.clone(), metadata, footnotes, md_options))
-            .collect();
-        // remove `None` children
-        let new_children: Vec<Node> = new_children?.iter().cloned().flatten().collect();
-        let _ = std::mem::replace(children, new_children);
-    }
-
-    Ok(match content {
-        Node::Heading(Heading {
-            children,
-            position,
-            depth,
-        }) => Some(Node::Html(Html {
-            value: render_heading(&children, depth, md_options)?,
-            position,
-        })),
-        Node::Code(Code {
-            value,
-            position,
-            lang,
-            ..
-        }) if lang.is_some() => {
-            let lang = lang.unwrap();
-            Some(Node::Html(Html {
-                value: syntax_highlight(&value, &lang)?,
-                position,
-            }))
-        }
-        Node::Math(Math {
-            value, position, ..
-        }) => Some(Node::Html(Html {
-            value: render_math(&value, false)?,
-            position,
-        })),
-        Node::InlineMath(InlineMath { value, position }) => Some(Node::Html(Html {
-            value: render_math(&value, true)?,
-            position,
-        })),
-        Node::Yaml(Yaml { value, .. }) => {
-            let new_metadata: PostMetadata = serde_yaml::from_str(&value)
-                .map_err(|e| PostLoadError::MetadataParseFailed(format!("{e:?}")))?;
-            let _ = metadata.insert(new_metadata);
-            None
-        }
-        Node::Toml(Toml { value, .. }) => {
-            let new_metadata: PostMetadata = toml::from_str(&value)
-                .map_err(|e| PostLoadError::MetadataParseFailed(format!("{e:?}")))?;
-            let _ = metadata.insert(new_metadata);
-            None
-        }
-        Node::FootnoteReference(FootnoteReference {
-            position,
-            identifier,
-            label,
-        }) => {
-            let Footnote {
-                definition_number,
-                reference_count,
-                defined,
-            } = if let Some(footnote) = footnotes.get_mut(&identifier) {
-                footnote.reference_count += 1;
-                footnote.clone()
-            } else {
-                let new_footnote = Footnote {
-                    definition_number: footnotes.len() + 1,
-                    reference_count: 1,
-                    defined: false,
-                };
-                footnotes.insert(identifier, new_footnote.clone());
-                new_footnote
-            };
-            Some(Node::Html(Html {
-                value: format!(
-                    "<sup><a class=\"footnote-ref\" href=\"#footnote-{definition_number}\" id=\"footnote-{definition_number}-ref-{reference_count}\" rel=\"external\">{definition_number}</a></sup>"
-                ),
-                position,
-            }))
-        }
-        Node::FootnoteDefinition(def) => match footnotes.get_mut(&def.identifier) {
-            Some(Footnote {
-                definition_number,
-                reference_count,
-                defined,
-            }) if *defined == false => {
-                *defined = true;
-                let html = render_footnote_definition(&def, md_options)?;
-                let ref_links_html = if *reference_count == 1 {
-                    format!(
-                        "<a class=\"footnote-ref-backlink\" href=\"#footnote-{definition_number}-ref-1\" rel=\"external\">↩</a>"
-                    )
-                } else {
-                    let mut s = String::new();
-                    for i in 1..=*reference_count {
-                        let next = format!(
-                            "<a class=\"footnote-ref-backlink\" href=\"#footnote-{definition_number}-ref-{i}\" rel=\"external\">↩<sup>{i}</sup></a>"
-                        );
-                        s.push_str(next.as_str());
-                    }
-                    s
-                };
-                Some(Node::Html(Html {
-                    value: format!(
-                        "<div class=\"footnote-def\" id=\"footnote-{definition_number}\"><p>[{definition_number}]: </p>{html}<span>{ref_links_html}</span></div>"
-                    ),
-                    position: def.position,
-                }))
+#[cfg(feature = "ssr")]
+impl Hooks for PostRenderer<'_> {
+    fn node(&mut self, node: &Node) -> Result<Option<String>, PostLoadError> {
+        use markdown::mdast::{
+            Blockquote, Code, FootnoteReference, Heading, InlineMath, Math, Toml, Yaml,
+        };
+
+        Ok(Some(match node {
+            Node::Text(text) => {
+                self.word_count += text.value.split_whitespace().count();
+                return Ok(None);
             }
-            Some(Footnote { defined: false, .. }) => {
-                unreachable!("defined == false is handled by the match arm above this")
+            Node::Paragraph(paragraph) => match split_figure(paragraph) {
+                Some((image, caption)) => self.render_figure(&image, &caption)?,
+                None => return Ok(None),
+            },
+            Node::Heading(Heading {
+                children, depth, ..
+            }) => self.render_heading(children, *depth)?,
+            Node::Blockquote(Blockquote { children, .. }) => {
+                let mut children = children.clone();
+                match take_callout_marker(&mut children) {
+                    Some(kind) => self.render_callout(kind, &children)?,
+                    None => return Ok(None),
+                }
             }
-            Some(Footnote { defined: true, .. }) => {
-                return Err(PostLoadError::MultipleFootnoteDefinitions(format!(
-                    "{}",
-                    def.identifier
-                )));
+            Node::Code(Code {
+                value,
+                lang: Some(lang),
+                ..
+            }) => timed("tree_sitter", || syntax_highlight(value, lang))?,
+            Node::Image(image) => render_image(image, self.site_root)?,
+            Node::Link(link) if is_external_url(&link.url) => self.render_external_link(link)?,
+            Node::Math(Math { value, .. }) => timed("katex", || render_math(value, false))?,
+            Node::InlineMath(InlineMath { value, .. }) => {
+                timed("katex", || render_math(value, true))?
             }
-            None => {
-                return Err(PostLoadError::FootnoteDefNotReferenced(def.identifier));
+            Node::Yaml(Yaml { value, .. }) => {
+                self.metadata = Some(parse_metadata(value, FrontMatter::Yaml)?);
+                String::new()
             }
-        },
-        c => Some(c),
-    })
+            Node::Toml(Toml { value, .. }) => {
+                self.metadata = Some(parse_metadata(value, FrontMatter::Toml)?);
+                String::new()
+            }
+            Node::FootnoteReference(FootnoteReference { identifier, .. }) => {
+                self.render_footnote_reference(identifier)
+            }
+            Node::FootnoteDefinition(definition) => self.render_footnote_definition(definition)?,
+            _ => return Ok(None),
+        }))
+    }
+
+    fn children(&mut self, children: &[Node]) -> Result<Option<String>, PostLoadError> {
+        render_with_directives(children, self.source, self).map(Some)
+    }
 }
 
 #[cfg(feature = "ssr")]
-fn render_heading(
-    children: &Vec<markdown::mdast::Node>,
-    depth: u8,
-    md_options: &markdown::Options,
-) -> Result<String, PostLoadError> {
-    use slugify::slugify;
+impl PostRenderer<'_> {
+    fn render_heading(&mut self, children: &[Node], depth: u8) -> Result<String, PostLoadError> {
+        use slugify::slugify;
 
-    let md =
-        mdast_util_to_markdown::to_markdown(&markdown::mdast::Node::Root(markdown::mdast::Root {
-            children: children.clone(),
-            position: None,
-        }))
-        .map_err(|e| {
-            eprintln!("render_heading to_markdown() failed: {e:?}");
-            PostLoadError::MarkdownParseFailed(e.reason)
-        })?;
+        let slug = slugify!(&to_text(children));
+        let inner_html = render_children(children, self)?;
+        Ok(format!(
+            "<div class=\"post-heading-container\"><a class=\"heading-anchor\" href=\"#{slug}\" rel=\"external\">&nbsp;&nbsp;</a><h{depth} id=\"{slug}\" class=\"post-heading\">{inner_html}</h{depth}></div>"
+        ))
+    }
 
-    let slug = slugify!(md.as_str());
+    fn render_footnote_reference(&mut self, identifier: &str) -> String {
+        let footnote_count = self.footnotes.len();
+        let footnote = self
+            .footnotes
+            .entry(identifier.to_string())
+            .and_modify(|footnote| footnote.reference_count += 1)
+            .or_insert(Footnote {
+                definition_number: footnote_count + 1,
+                reference_count: 1,
+                defined: false,
+            });
+        let Footnote {
+            definition_number,
+            reference_count,
+            ..
+        } = footnote;
+        format!(
+            "<sup><a class=\"footnote-ref\" href=\"#footnote-{definition_number}\" id=\"footnote-{definition_number}-ref-{reference_count}\" rel=\"external\">{definition_number}</a></sup>"
+        )
+    }
 
-    let inner_html = markdown::to_html_with_options(&md, md_options).map_err(|e| {
-        eprintln!("render_heading to_html_with_options() failed: {e:?}");
-        PostLoadError::MarkdownParseFailed(e.reason)
-    })?;
-    Ok(format!(
-        "<div class=\"post-heading-container\"><a class=\"heading-anchor\" href=\"#{slug}\" rel=\"external\">&nbsp;&nbsp;</a><h{depth} id=\"{slug}\" class=\"post-heading\">{inner_html}</h{depth}></div>"
-    ))
+    fn render_footnote_definition(
+        &mut self,
+        definition: &markdown::mdast::FootnoteDefinition,
+    ) -> Result<String, PostLoadError> {
+        let (definition_number, reference_count) =
+            match self.footnotes.get_mut(&definition.identifier) {
+                Some(footnote) if !footnote.defined => {
+                    footnote.defined = true;
+                    (footnote.definition_number, footnote.reference_count)
+                }
+                Some(_) => {
+                    return Err(PostLoadError::MultipleFootnoteDefinitions(
+                        definition.identifier.clone(),
+                    ));
+                }
+                None => {
+                    return Err(PostLoadError::FootnoteDefNotReferenced(
+                        definition.identifier.clone(),
+                    ));
+                }
+            };
+
+        let html = render_children(&definition.children, self)?;
+        let ref_links_html = if reference_count == 1 {
+            format!(
+                "<a class=\"footnote-ref-backlink\" href=\"#footnote-{definition_number}-ref-1\" rel=\"external\">↩</a>"
+            )
+        } else {
+            let mut s = String::new();
+            for i in 1..=reference_count {
+                let next = format!(
+                    "<a class=\"footnote-ref-backlink\" href=\"#footnote-{definition_number}-ref-{i}\" rel=\"external\">↩<sup>{i}</sup></a>"
+                );
+                s.push_str(next.as_str());
+            }
+            s
+        };
+        Ok(format!(
+            "<div class=\"footnote-def\" id=\"footnote-{definition_number}\"><p>[{definition_number}]: </p>{html}<span>{ref_links_html}</span></div>"
+        ))
+    }
+
+    /// Renders a numbered `<figure>`, which can be linked to as `#figure-<number>`
+    fn render_figure(
+        &mut self,
+        image: &markdown::mdast::Image,
+        caption: &[Node],
+    ) -> Result<String, PostLoadError> {
+        self.figure_count += 1;
+        let number = self.figure_count;
+        let caption_html = if caption.is_empty() {
+            html_escape::encode_text(image.title.as_deref().unwrap_or_default()).to_string()
+        } else {
+            render_children(caption, self)?
+        };
+        Ok(format!(
+            "<figure id=\"figure-{number}\" class=\"post-figure\">{}<figcaption><a class=\"figure-anchor\" href=\"#figure-{number}\" rel=\"external\">Figure {number}</a>: {caption_html}</figcaption></figure>",
+            render_image(image, self.site_root)?
+        ))
+    }
+
+    /// Renders an external link, opened without giving the target site access to this one
+    fn render_external_link(
+        &mut self,
+        link: &markdown::mdast::Link,
+    ) -> Result<String, PostLoadError> {
+        let title = link
+            .title
+            .as_deref()
+            .map(|title| {
+                format!(
+                    " title=\"{}\"",
+                    html_escape::encode_double_quoted_attribute(title)
+                )
+            })
+            .unwrap_or_default();
+        Ok(format!(
+            "<a class=\"external-link\" href=\"{}\"{title} rel=\"external noopener noreferrer\">{}{}</a>",
+            html_escape::encode_double_quoted_attribute(&link.url),
+            render_children(&link.children, self)?,
+            render_icon(icondata::LuExternalLink),
+        ))
+    }
+
+    fn render_callout(
+        &mut self,
+        kind: CalloutKind,
+        children: &[Node],
+    ) -> Result<String, PostLoadError> {
+        let html = render_children(children, self)?;
+        Ok(format!(
+            "<div class=\"callout {}\"><p class=\"callout-title\">{}{}</p>{html}</div>",
+            kind.class(),
+            render_icon(kind.icon()),
+            kind.title(),
+        ))
+    }
 }
 
+/// Splits a paragraph holding nothing but an image into the image and its caption
+///
+/// The caption is either written on the lines right after the image, or taken
+/// from the title of the image. Images without either are not figures.
 #[cfg(feature = "ssr")]
-fn render_footnote_definition(
-    node: &markdown::mdast::FootnoteDefinition,
-    md_options: &markdown::Options,
-) -> Result<String, PostLoadError> {
-    let md =
-        mdast_util_to_markdown::to_markdown(&markdown::mdast::Node::Root(markdown::mdast::Root {
-            children: node.children.clone(),
-            position: None,
-        }))
-        .map_err(|e| {
-            eprintln!("render_footnote_definition to_markdown() failed: {e:?}");
-            PostLoadError::MarkdownParseFailed(e.reason)
-        })?;
-    let html = markdown::to_html_with_options(&md, md_options).map_err(|e| {
-        eprintln!("render_footnote_definition to_html_with_options() failed: {e:?}");
-        PostLoadError::MarkdownParseFailed(e.reason)
-    })?;
-    Ok(html)
+fn split_figure(
+    paragraph: &markdown::mdast::Paragraph,
+) -> Option<(markdown::mdast::Image, Vec<markdown::mdast::Node>)> {
+    use markdown::mdast::Node;
+
+    let (Node::Image(image), rest) = paragraph.children.split_first()? else {
+        return None;
+    };
+    let mut caption = rest.to_vec();
+    match caption.first_mut() {
+        None => {}
+        Some(Node::Break(_)) => {
+            caption.remove(0);
+        }
+        // text on the same line as the image makes it an inline image
+        Some(Node::Text(text)) if text.value.starts_with('\n') => {
+            text.value = text.value.trim_start().to_string();
+            if text.value.is_empty() {
+                caption.remove(0);
+            }
+        }
+        Some(_) => return None,
+    }
+
+    if caption.is_empty() && image.title.is_none() {
+        return None;
+    }
+    Some((image.clone(), caption))
+}
+
+/// Kind of a GitHub-style callout, e.g. `> [!NOTE]`
+#[cfg(feature = "ssr")]
+#[derive(Clone, Copy)]
+enum CalloutKind {
+    Note,
+    Warning,
+    Tip,
+}
+
+#[cfg(feature = "ssr")]
+impl CalloutKind {
+    fn from_marker(text: &str) -> Option<(Self, &str)> {
+        [
+            ("[!NOTE]", Self::Note),
+            ("[!WARNING]", Self::Warning),
+            ("[!TIP]", Self::Tip),
+        ]
+        .into_iter()
+        .find_map(|(marker, kind)| text.strip_prefix(marker).map(|rest| (kind, rest)))
+    }
+
+    fn title(&self) -> &'static str {
+        match self {
+            Self::Note => "Note",
+            Self::Warning => "Warning",
+            Self::Tip => "Tip",
+        }
+    }
+
+    fn class(&self) -> &'static str {
+        match self {
+            Self::Note => "callout-note",
+            Self::Warning => "callout-warning",
+            Self::Tip => "callout-tip",
+        }
+    }
+
+    fn icon(&self) -> icondata::Icon {
+        match self {
+            Self::Note => icondata::LuInfo,
+            Self::Warning => icondata::LuTriangleAlert,
+            Self::Tip => icondata::LuLightbulb,
+        }
+    }
+}
+
+/// Strips the `[!KIND]` marker from the start of a blockquote, if it has one
+#[cfg(feature = "ssr")]
+fn take_callout_marker(children: &mut Vec<markdown::mdast::Node>) -> Option<CalloutKind> {
+    use markdown::mdast::Node;
+
+    let Some(Node::Paragraph(paragraph)) = children.first_mut() else {
+        return None;
+    };
+    let Some(Node::Text(text)) = paragraph.children.first_mut() else {
+        return None;
+    };
+    let (kind, rest) = CalloutKind::from_marker(&text.value)?;
+    let rest = rest.trim_start().to_string();
+
+    if rest.is_empty() {
+        paragraph.children.remove(0);
+    } else {
+        text.value = rest;
+    }
+    if paragraph.children.is_empty() {
+        children.remove(0);
+    }
+    Some(kind)
+}
+
+/// Renders an icon from `icondata` as an inline `<svg>` element
+#[cfg(feature = "ssr")]
+fn render_icon(icon: icondata::Icon) -> String {
+    let attributes = [
+        ("viewBox", icon.view_box),
+        ("style", icon.style),
+        ("fill", icon.fill),
+        ("stroke", icon.stroke),
+        ("stroke-width", icon.stroke_width),
+        ("stroke-linecap", icon.stroke_linecap),
+        ("stroke-linejoin", icon.stroke_linejoin),
+    ]
+    .into_iter()
+    .filter_map(|(name, value)| value.map(|value| format!(" {name}=\"{value}\"")))
+    .collect::<String>();
+    format!(
+        "<svg class=\"icon\" width=\"1em\" height=\"1em\" aria-hidden=\"true\"{attributes}>{}</svg>",
+        icon.data
+    )
 }
 
+/// Math to render, with the channel to send the result back on
 #[cfg(feature = "ssr")]
+type MathJob = (
+    String,
+    katex::Opts,
+    std::sync::mpsc::Sender<Result<String, katex::Error>>,
+);
+
+/// Sends math to a thread rendering it with KaTeX
+///
+/// The JS engine of KaTeX fails when it's called deeper in the stack than where
+/// it was first used on a thread, which happens when posts are rendered inside
+/// other requests, so it's always called from the same place.
+#[cfg(feature = "ssr")]
+static MATH_RENDERER: std::sync::LazyLock<std::sync::mpsc::Sender<MathJob>> =
+    std::sync::LazyLock::new(|| {
+        let (sender, receiver) = std::sync::mpsc::channel::<MathJob>();
+        std::thread::spawn(move || {
+            for (src, opts, result) in receiver {
+                let _ = result.send(katex::render_with_opts(&src, &opts));
+            }
+        });
+        sender
+    });
+
+#[cfg(feature = "ssr")]
+#[tracing::instrument(level = "debug", skip(src))]
 fn render_math(src: &str, inline: bool) -> Result<String, PostLoadError> {
     let opts = katex::Opts::builder()
         .display_mode(!inline)
         .build()
         .map_err(|e| PostLoadError::RenderMathFailed(format!("{e:?}")))?;
-    let html = katex::render_with_opts(src, &opts)
+    let (sender, receiver) = std::sync::mpsc::channel();
+    MATH_RENDERER
+        .send((src.to_string(), opts, sender))
+        .map_err(|e| PostLoadError::RenderMathFailed(format!("{e:?}")))?;
+    let html = receiver
+        .recv()
+        .map_err(|e| PostLoadError::RenderMathFailed(format!("{e:?}")))?
         .map_err(|e| PostLoadError::RenderMathFailed(format!("{e:?}")))?;
     if inline {
         Ok(html)
@@ -417,6 +980,7 @@ fn render_math(src: &str, inline: bool) -> Result<String, PostLoadError> {
 }
 
 #[cfg(feature = "ssr")]
+#[tracing::instrument(level = "debug", skip(src))]
 fn syntax_highlight(src: &str, lang: &str) -> Result<String, PostLoadError> {
     // TODO: support all highlight types
     let highlight_names = [
@@ -516,3 +1080,171 @@ fn syntax_highlight(src: &str, lang: &str) -> Result<String, PostLoadError> {
         "<pre><code class=\"language-{lang}\">{highlighted_src}</code></pre>"
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn post_load_errors_round_trip_through_server_fn_encoding() {
+        let errors = [
+            PostLoadError::InvalidId(PostIdError::ParseNumberFailed),
+            PostLoadError::InvalidId(PostIdError::InvalidFormat),
+            PostLoadError::InvalidId(PostIdError::Missing),
+            PostLoadError::MarkdownParseFailed("unexpected end".to_string()),
+            PostLoadError::SyntaxHighlightFailed("cobol not implemented :(".to_string()),
+            PostLoadError::RenderMathFailed("undefined control sequence".to_string()),
+            PostLoadError::NotFound,
+            PostLoadError::NoMetadata,
+            PostLoadError::MetadataParseFailed("missing field `title`".to_string()),
+            PostLoadError::FootnoteDefNotReferenced("note".to_string()),
+            PostLoadError::MultipleFootnoteDefinitions("note".to_string()),
+            PostLoadError::ImageProcessingFailed("unsupported format".to_string()),
+            PostLoadError::AuthorsLoadFailed("unknown author `nobody`".to_string()),
+            PostLoadError::InvalidDirective {
+                reason: "missing video id".to_string(),
+                line: 3,
+                column: 5,
+            },
+            PostLoadError::ServerFn(ServerFnErrorErr::Registration("poisoned".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::UnsupportedRequestMethod(
+                "TRACE".to_string(),
+            )),
+            PostLoadError::ServerFn(ServerFnErrorErr::Request("offline".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::ServerError(
+                "failed to read post file".to_string(),
+            )),
+            PostLoadError::ServerFn(ServerFnErrorErr::MiddlewareError("denied".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::Deserialization("eof".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::Serialization("eof".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::Args("post_id".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::MissingArg("post_id".to_string())),
+            PostLoadError::ServerFn(ServerFnErrorErr::Response("headers".to_string())),
+        ];
+        for error in errors {
+            assert_eq!(PostLoadError::de(error.ser()), error);
+        }
+    }
+
+    /// Top level nodes of a markdown document
+    #[cfg(feature = "ssr")]
+    fn parse(source: &str) -> Vec<Node> {
+        let mdast = markdown::to_mdast(source, &markdown::ParseOptions::gfm()).unwrap();
+        mdast.children().cloned().unwrap_or_default()
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn callout_markers_are_taken_from_blockquotes() {
+        let callout = |source: &str| {
+            let nodes = parse(source);
+            let [Node::Blockquote(blockquote)] = nodes.as_slice() else {
+                panic!("expected a single blockquote");
+            };
+            let mut children = blockquote.children.clone();
+            let kind = take_callout_marker(&mut children);
+            (kind, children)
+        };
+
+        let (kind, children) = callout("> [!NOTE] Read this first\n> and this too");
+        assert!(matches!(kind, Some(CalloutKind::Note)));
+        assert_eq!(to_text(&children), "Read this first\nand this too");
+
+        // the marker on a line of its own leaves no empty paragraph behind
+        let (kind, children) = callout("> [!WARNING]\n>\n> Careful");
+        assert!(matches!(kind, Some(CalloutKind::Warning)));
+        assert_eq!(children.len(), 1);
+        assert_eq!(to_text(&children), "Careful");
+
+        for source in [
+            "> Just a quote",
+            "> [!DANGER] Unknown kind",
+            "> **[!TIP]** Not first",
+        ] {
+            let (kind, _) = callout(source);
+            assert!(kind.is_none(), "{source}");
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn captions_are_split_from_standalone_images() {
+        let figure = |source: &str| {
+            let nodes = parse(source);
+            let [Node::Paragraph(paragraph)] = nodes.as_slice() else {
+                panic!("expected a single paragraph");
+            };
+            split_figure(paragraph).map(|(image, caption)| (image.url, to_text(&caption)))
+        };
+
+        assert_eq!(
+            figure("![a sunflower](sunflower.webp)\nA *nice* sunflower"),
+            Some(("sunflower.webp".to_string(), "A nice sunflower".to_string()))
+        );
+        assert_eq!(
+            figure("![a sunflower](sunflower.webp)\\\nA sunflower"),
+            Some(("sunflower.webp".to_string(), "A sunflower".to_string()))
+        );
+        // the title is rendered as the caption
+        assert_eq!(
+            figure("![a sunflower](sunflower.webp \"A sunflower\")"),
+            Some(("sunflower.webp".to_string(), String::new()))
+        );
+
+        for source in [
+            "![a sunflower](sunflower.webp)",
+            "![a sunflower](sunflower.webp) inline",
+            "Text before ![a sunflower](sunflower.webp)",
+        ] {
+            assert_eq!(figure(source), None, "{source}");
+        }
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn metadata_is_read_from_yaml_and_toml_front_matter() {
+        let yaml = read_metadata(
+            "---\ntitle: Hello\ndescription: A post\ndate: 2025-07-19\nauthor: someone\ndraft: true\n---\n\n# Hello\n",
+        )
+        .unwrap();
+        assert_eq!(yaml.title, "Hello");
+        assert_eq!(
+            yaml.date,
+            chrono::NaiveDate::from_ymd_opt(2025, 7, 19).unwrap()
+        );
+        assert_eq!(yaml.authors, ["someone"]);
+        assert!(yaml.draft);
+
+        let toml = read_metadata(
+            "+++\ntitle = \"Hello\"\ndescription = \"A post\"\ndate = \"2025-07-19\"\nauthors = [\"someone\", \"someone-else\"]\n+++\n",
+        )
+        .unwrap();
+        assert_eq!(toml.authors, ["someone", "someone-else"]);
+        assert!(!toml.draft);
+
+        let defaults =
+            read_metadata("---\ntitle: Hello\ndescription: A post\ndate: 2025-07-19\n---\n")
+                .unwrap();
+        assert_eq!(defaults.authors, default_authors());
+        assert!(defaults.tags.is_empty());
+    }
+
+    #[cfg(feature = "ssr")]
+    #[test]
+    fn front_matter_has_to_be_fenced() {
+        for source in [
+            "# No front matter\n",
+            "---\ntitle: Never closed\n",
+            "---\ntitle: Mixed fences\n+++\n",
+        ] {
+            assert_eq!(
+                read_metadata(source).unwrap_err(),
+                PostLoadError::NoMetadata
+            );
+        }
+        assert!(matches!(
+            read_metadata("---\ntitle: Missing date\ndescription: A post\n---\n"),
+            Err(PostLoadError::MetadataParseFailed(_))
+        ));
+    }
+}
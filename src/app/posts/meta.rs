@@ -0,0 +1,98 @@
+use super::super::site_url;
+use super::id::PostId;
+use super::load::Post;
+use leptos::prelude::*;
+use leptos_meta::*;
+
+/// Absolute URL of a path on the site, other URLs are left as they are
+fn absolute_url(url: &str) -> String {
+    if url.starts_with('/') && !url.starts_with("//") {
+        format!("{}{url}", site_url())
+    } else {
+        url.to_string()
+    }
+}
+
+/// Title, description, OpenGraph and Twitter card tags, canonical link and
+/// `schema.org` JSON-LD of a post
+#[component]
+pub fn PostMetaTags(post: Post, post_id: PostId) -> impl IntoView {
+    let metadata = post.metadata;
+    let site_url = site_url();
+    let url = format!("{site_url}/posts/{post_id}");
+    // posts without an image of their own get a generated preview card
+    let image = match &metadata.image {
+        Some(image) => absolute_url(image),
+        None => format!("{site_url}/og/{post_id}.png"),
+    };
+    let published = metadata.date.to_string();
+    let modified = metadata
+        .updated
+        .map(|updated| updated.to_string())
+        .unwrap_or_else(|| published.clone());
+    let author_urls: Vec<_> = post
+        .authors
+        .iter()
+        .map(|author| format!("{site_url}/authors/{}", author.id))
+        .collect();
+
+    let json_ld = serde_json::json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": metadata.title,
+        "description": metadata.description,
+        "image": image,
+        "url": url,
+        "mainEntityOfPage": url,
+        "datePublished": published,
+        "dateModified": modified,
+        "keywords": metadata.tags,
+        "wordCount": post.word_count,
+        "author": post
+            .authors
+            .iter()
+            .zip(&author_urls)
+            .map(|(author, url)| serde_json::json!({
+                "@type": "Person",
+                "name": author.name,
+                "url": url,
+            }))
+            .collect::<Vec<_>>(),
+    })
+    .to_string()
+    // the JSON ends up in a `<script>`, which can't contain its own end tag
+    .replace("</", "<\\/");
+
+    view! {
+        <Title text=metadata.title.clone() />
+        <Meta name="description" content=metadata.description.clone() />
+        <Link rel="canonical" href=url.clone() />
+        // drafts are not in the sitemap, and shouldn't be found through search engines either
+        {metadata.draft.then(|| view! { <Meta name="robots" content="noindex" /> })}
+
+        <Meta property="og:type" content="article" />
+        <Meta property="og:site_name" content="akosnad.dev" />
+        <Meta property="og:title" content=metadata.title.clone() />
+        <Meta property="og:description" content=metadata.description.clone() />
+        <Meta property="og:url" content=url />
+        <Meta property="og:image" content=image.clone() />
+        <Meta property="article:published_time" content=published />
+        <Meta property="article:modified_time" content=modified />
+        {author_urls
+            .into_iter()
+            .map(|url| view! { <Meta property="article:author" content=url /> })
+            .collect::<Vec<_>>()}
+        {metadata
+            .tags
+            .into_iter()
+            .map(|tag| view! { <Meta property="article:tag" content=tag /> })
+            .collect::<Vec<_>>()}
+
+        <Meta name="twitter:card" content="summary_large_image" />
+        <Meta name="twitter:title" content=metadata.title />
+        <Meta name="twitter:description" content=metadata.description />
+        <Meta name="twitter:image" content=image />
+
+        <Script type_="application/ld+json">{json_ld}</Script>
+    }
+}
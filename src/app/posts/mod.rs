@@ -1,13 +1,72 @@
+use super::ErrorPage;
 use leptos::prelude::*;
 use leptos_meta::*;
 use leptos_router::hooks::use_params_map;
 use std::str::FromStr as _;
 
+#[cfg(feature = "ssr")]
+mod directive;
+
+mod error;
+use error::*;
+
+#[cfg(feature = "ssr")]
+mod images;
+#[cfg(feature = "ssr")]
+pub use images::{
+    CACHE_URL as IMAGE_CACHE_URL, cache_dir as image_cache_dir,
+    set_cache_dir as set_image_cache_dir,
+};
+
+#[cfg(feature = "ssr")]
+mod links;
+
+#[cfg(feature = "ssr")]
+mod preview;
+#[cfg(feature = "ssr")]
+pub use preview::preview_image;
+
+#[cfg(feature = "ssr")]
+mod render;
+
+#[cfg(feature = "ssr")]
+mod sanitize;
+
 mod id;
+pub use id::PostId;
 use id::*;
 
+mod meta;
+use meta::*;
+
 mod load;
 use load::*;
+#[cfg(feature = "ssr")]
+pub use load::{PostLoadError, check_post_links};
+
+#[cfg(feature = "ssr")]
+mod index;
+#[cfg(feature = "ssr")]
+pub use index::{failing_posts, list_posts};
+
+mod navigation;
+use navigation::*;
+
+mod related;
+use related::*;
+
+/// Builds the post index and renders the published posts again, to pick up
+/// changes without waiting for the caches to expire
+///
+/// The current index and rendered posts are served until the new ones are
+/// ready, and the index is kept if building the new one fails. Returns the
+/// number of published posts.
+#[cfg(feature = "ssr")]
+pub fn reload_posts() -> Result<usize, PostLoadError> {
+    let index = index::rebuild_post_index()?;
+    refresh_post_cache(index.posts.iter().map(|(post_id, _)| post_id.clone()));
+    Ok(index.posts.len())
+}
 
 #[component]
 pub fn PostsList() -> impl IntoView {
@@ -38,9 +97,9 @@ pub fn PostContent() -> impl IntoView {
             .and_then(|s| PostId::from_str(&s));
         #[cfg(feature = "ssr")]
         {
-            if let Err(_) = result {
+            if let Err(e) = result {
                 let resp = expect_context::<leptos_actix::ResponseOptions>();
-                resp.set_status(actix_web::http::StatusCode::BAD_REQUEST);
+                resp.set_status(PostLoadError::InvalidId(e).status_code());
             }
         }
         result
@@ -48,25 +107,25 @@ pub fn PostContent() -> impl IntoView {
 
     let post = Resource::new(post_id, move |post_id| async move {
         match post_id {
-            Ok(id) => load_post_content(id).await,
-            Err(e) => Err(PostLoadError::InvalidId(e).into()),
+            Ok(id) => load_post_content(id.clone()).await.map(|post| (id, post)),
+            Err(e) => Err(PostLoadError::InvalidId(e)),
         }
     });
 
     view! {
-        <ErrorBoundary fallback=|errors| {
-            view! {
-                <div class="error">
-                    <ul>
-                        {move || {
-                            errors
-                                .get()
-                                .into_iter()
-                                .map(|(_, e)| view! { <li>{e.to_string()}</li> })
-                                .collect::<Vec<_>>()
-                        }}
-                    </ul>
-                </div>
+        <ErrorBoundary fallback=move |errors| {
+            move || {
+                errors
+                    .get()
+                    .into_iter()
+                    .next()
+                    .map(|(_, e)| match e.downcast_ref::<PostLoadError>() {
+                        Some(e) => {
+                            view! { <PostError error=e.clone() post_id=post_id().ok() /> }
+                                .into_any()
+                        }
+                        None => view! { <ErrorPage heading="Error" /> }.into_any(),
+                    })
             }
         }>
             <Suspense fallback=move || {
@@ -76,7 +135,9 @@ pub fn PostContent() -> impl IntoView {
                     post.read()
                         .as_ref()
                         .cloned()
-                        .map(|res| { res.map(|p| view! { <PostBody post=p /> }) })
+                        .map(|res| {
+                            res.map(|(post_id, post)| view! { <PostBody post post_id /> })
+                        })
                 }}
             </Suspense>
         </ErrorBoundary>
@@ -84,31 +145,64 @@ pub fn PostContent() -> impl IntoView {
 }
 
 #[component]
-fn PostBody(post: Post) -> impl IntoView {
-    let author = post.metadata.author.clone();
+fn PostBody(post: Post, post_id: PostId) -> impl IntoView {
+    let navigation = Resource::new(
+        {
+            let post_id = post_id.clone();
+            move || post_id.clone()
+        },
+        load_post_navigation,
+    );
+
     view! {
-        <Meta name="description" content=post.metadata.description />
-        <Title text=post.metadata.title.clone() />
+        <PostMetaTags post=post.clone() post_id=post_id.clone() />
         <div id="post-metadata">
             <h1 id="post-title" class="text-4xl font-extrabold" aria-label="Post title">
                 {post.metadata.title}
             </h1>
             <div class="flex flex-row flex-wrap gap-4">
-                <p id="post-author" aria-label="Post author">
-                    <span id="post-author-avatar" aria-hidden="true">
-                        <img
-                            src=move || format!("/assets/{author}.webp")
-                            alt="post author avatar"
-                        />
-                    </span>
-                    {post.metadata.author}
+                <p id="post-authors" aria-label="Post authors">
+                    {post
+                        .authors
+                        .into_iter()
+                        .map(|author| {
+                            view! {
+                                <a class="post-author" href=format!("/authors/{}", author.id)>
+                                    <span class="post-author-avatar" aria-hidden="true">
+                                        <img src=author.avatar alt="post author avatar" />
+                                    </span>
+                                    {author.name}
+                                </a>
+                            }
+                        })
+                        .collect::<Vec<_>>()}
                 </p>
                 <time id="post-date" aria-label="Post date" datetime=post.metadata.date.to_string()>
                     {post.metadata.date.to_string()}
                 </time>
+                <p id="post-reading-time" aria-label="Reading time">
+                    {format!("{} min read", post.reading_time)}
+                </p>
+                {post
+                    .metadata
+                    .updated
+                    .filter(|updated| *updated > post.metadata.date)
+                    .map(|updated| {
+                        view! {
+                            <p id="post-updated">
+                                "updated on "
+                                <time aria-label="Post updated" datetime=updated.to_string()>
+                                    {updated.to_string()}
+                                </time>
+                            </p>
+                        }
+                    })}
             </div>
             <hr />
         </div>
+        <SeriesBox post_id=post_id.clone() navigation />
         <article id="post-body" inner_html=post.html />
+        <PrevNextLinks navigation />
+        <RelatedPosts post_id />
     }
 }
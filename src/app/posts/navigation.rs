@@ -0,0 +1,175 @@
+use super::id::PostId;
+use super::load::PostLoadError;
+use leptos::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A post linked from another one
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct PostLink {
+    pub id: PostId,
+    pub title: String,
+}
+
+/// A series a post is part of, with every part in order
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct SeriesParts {
+    pub name: String,
+    pub parts: Vec<PostLink>,
+}
+
+/// Links from a post to the posts around it
+///
+/// Parts of a series link to the parts before and after them, other posts
+/// link to the posts before and after them by number.
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct PostNavigation {
+    pub series: Option<SeriesParts>,
+    pub previous: Option<PostLink>,
+    pub next: Option<PostLink>,
+}
+
+#[server]
+pub async fn load_post_navigation(post_id: PostId) -> Result<PostNavigation, PostLoadError> {
+    let posts = super::index::list_posts()?;
+    let Some((_, metadata)) = posts.iter().find(|(id, _)| id.number == post_id.number) else {
+        return Err(PostLoadError::NotFound);
+    };
+    let link = |(id, metadata): &(PostId, super::load::PostMetadata)| PostLink {
+        id: id.clone(),
+        title: metadata.title.clone(),
+    };
+
+    let (series, neighbors) = match &metadata.series {
+        Some(series) => {
+            let mut parts: Vec<_> = posts
+                .iter()
+                .filter(|(_, part)| part.series.as_ref().is_some_and(|s| s.name == series.name))
+                .collect();
+            parts.sort_by_key(|(_, part)| part.series.as_ref().map(|s| s.order));
+            let parts: Vec<_> = parts.into_iter().map(link).collect();
+            let series = SeriesParts {
+                name: series.name.clone(),
+                parts: parts.clone(),
+            };
+            (Some(series), parts)
+        }
+        None => {
+            let mut neighbors: Vec<_> = posts.iter().map(link).collect();
+            neighbors.sort_by_key(|link| link.id.number);
+            (None, neighbors)
+        }
+    };
+
+    let position = neighbors
+        .iter()
+        .position(|link| link.id.number == post_id.number);
+    let previous = position
+        .and_then(|i| i.checked_sub(1))
+        .and_then(|i| neighbors.get(i))
+        .cloned();
+    let next = position.and_then(|i| neighbors.get(i + 1)).cloned();
+
+    Ok(PostNavigation {
+        series,
+        previous,
+        next,
+    })
+}
+
+/// Every part of the series of a post, with the current one marked
+#[component]
+pub fn SeriesBox(
+    post_id: PostId,
+    navigation: Resource<Result<PostNavigation, PostLoadError>>,
+) -> impl IntoView {
+    view! {
+        <Suspense>
+            {move || {
+                let post_id = post_id.clone();
+                navigation
+                    .get()
+                    .and_then(Result::ok)
+                    .and_then(|navigation| navigation.series)
+                    .map(move |series| {
+                        let position = series
+                            .parts
+                            .iter()
+                            .position(|part| part.id.number == post_id.number)
+                            .map(|i| i + 1)
+                            .unwrap_or_default();
+                        view! {
+                            <nav id="post-series" aria-label="Post series">
+                                <p>
+                                    {format!("Part {position} of {} in ", series.parts.len())}
+                                    <strong>{series.name}</strong>
+                                </p>
+                                <ol>
+                                    {series
+                                        .parts
+                                        .into_iter()
+                                        .map(|part| {
+                                            let current = (part.id.number == post_id.number)
+                                                .then_some("page");
+                                            view! {
+                                                <li>
+                                                    <a
+                                                        href=format!("/posts/{}", part.id)
+                                                        aria-current=current
+                                                    >
+                                                        {part.title}
+                                                    </a>
+                                                </li>
+                                            }
+                                        })
+                                        .collect::<Vec<_>>()}
+                                </ol>
+                            </nav>
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
+
+/// Links to the previous and the next post
+#[component]
+pub fn PrevNextLinks(navigation: Resource<Result<PostNavigation, PostLoadError>>) -> impl IntoView {
+    view! {
+        <Suspense>
+            {move || {
+                navigation
+                    .get()
+                    .and_then(Result::ok)
+                    .filter(|navigation| navigation.previous.is_some() || navigation.next.is_some())
+                    .map(|navigation| {
+                        let previous = navigation
+                            .previous
+                            .map(|post| {
+                                view! {
+                                    <a class="previous-post" href=format!("/posts/{}", post.id) rel="prev">
+                                        <span>"Previous"</span>
+                                        {post.title}
+                                    </a>
+                                }
+                            });
+                        let next = navigation
+                            .next
+                            .map(|post| {
+                                view! {
+                                    <a class="next-post" href=format!("/posts/{}", post.id) rel="next">
+                                        <span>"Next"</span>
+                                        {post.title}
+                                    </a>
+                                }
+                            });
+                        view! {
+                            <nav id="post-navigation" aria-label="Previous and next posts">
+                                {previous}
+                                {next}
+                            </nav>
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
@@ -0,0 +1,211 @@
+//! Social preview images of posts
+//!
+//! The title, date and authors of a post are laid out as SVG on top of a
+//! halftone pattern like the backdrop of the site, then rasterized with the
+//! site font. The PNG is cached next to the image variants, and generated again
+//! when the post, the authors registry or the avatars of its authors change.
+
+use super::id::PostId;
+use super::images::{cache_dir, is_up_to_date};
+use super::load::{Post, PostLoadError, load_post};
+use base64::Engine as _;
+use html_escape::{encode_double_quoted_attribute as escape_attribute, encode_text as escape};
+use resvg::{tiny_skia, usvg};
+use std::path::{Path, PathBuf};
+
+const WIDTH: u32 = 1200;
+const HEIGHT: u32 = 630;
+
+/// TTF copy of the site font, written by `build.rs` next to the web font
+const FONT_FILE: &str = "site-font.ttf";
+
+/// Distance between the dots of the halftone pattern
+const DOT_SPACING: u32 = 14;
+
+/// Title lines are wrapped at this many characters, which roughly fill the card
+const TITLE_LINE_LENGTH: usize = 28;
+const TITLE_MAX_LINES: usize = 3;
+
+/// Size of the author avatars on the card
+const AVATAR_SIZE: u32 = 72;
+
+/// Path of the preview image of a post, generated if it's missing or outdated
+pub fn preview_image(post_id: PostId) -> Result<PathBuf, PostLoadError> {
+    let config = leptos::config::get_configuration(None).unwrap();
+    let site_root = config.leptos_options.site_root.to_string();
+    let err = |e: &dyn std::fmt::Display| {
+        let e = PostLoadError::ImageProcessingFailed(format!("preview of {post_id}: {e}"));
+        e.count();
+        e
+    };
+
+    let post = load_post(post_id.clone())?;
+    let path = cache_dir().join(format!("og/{post_id}.png"));
+    let source = Path::new(&site_root).join(format!("posts/{post_id}.md"));
+    // the registry and local avatars are optional, missing ones can't make the card outdated
+    let authors = std::iter::once(Path::new(&site_root).join("authors.toml")).chain(
+        post.authors
+            .iter()
+            .filter_map(|author| asset_path(&author.avatar, &site_root)),
+    );
+    if is_up_to_date(&path, &source)
+        && authors
+            .filter(|file| file.exists())
+            .all(|file| is_up_to_date(&path, &file))
+    {
+        return Ok(path);
+    }
+
+    let mut options = usvg::Options::default();
+    let font = std::fs::read(Path::new(&site_root).join(FONT_FILE)).map_err(|e| err(&e))?;
+    options.fontdb_mut().load_font_data(font);
+    let family = options
+        .fontdb_mut()
+        .faces()
+        .next()
+        .and_then(|face| face.families.first())
+        .map(|(family, _)| family.clone())
+        .ok_or_else(|| err(&"no font found in the font file"))?;
+    options.font_family = family;
+
+    let svg = card_svg(&post, &site_root);
+    let tree = usvg::Tree::from_str(&svg, &options).map_err(|e| err(&e))?;
+    let mut pixmap = tiny_skia::Pixmap::new(WIDTH, HEIGHT).ok_or_else(|| err(&"empty image"))?;
+    resvg::render(&tree, tiny_skia::Transform::default(), &mut pixmap.as_mut());
+    let png = pixmap.encode_png().map_err(|e| err(&e))?;
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| err(&e))?;
+    }
+    std::fs::write(&path, png).map_err(|e| err(&e))?;
+    Ok(path)
+}
+
+/// SVG of the preview card, text uses the default font family of the renderer
+fn card_svg(post: &Post, site_root: &str) -> String {
+    let title = wrap_title(&post.metadata.title)
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            format!(
+                r#"<tspan x="110" y="{}">{}</tspan>"#,
+                190 + i * 80,
+                escape(line)
+            )
+        })
+        .collect::<String>();
+
+    let mut avatars = String::new();
+    for (i, author) in post.authors.iter().enumerate() {
+        let Some(avatar) = avatar_data_url(&author.avatar, site_root) else {
+            continue;
+        };
+        let x = 110 + i as u32 * (AVATAR_SIZE - 16);
+        let (cx, cy, r) = (x + AVATAR_SIZE / 2, 440 + AVATAR_SIZE / 2, AVATAR_SIZE / 2);
+        avatars.push_str(&format!(
+            r##"<clipPath id="avatar-{i}"><circle cx="{cx}" cy="{cy}" r="{r}"/></clipPath><circle cx="{cx}" cy="{cy}" r="{border}" fill="#0f172a"/><image x="{x}" y="440" width="{AVATAR_SIZE}" height="{AVATAR_SIZE}" href="{href}" clip-path="url(#avatar-{i})"/>"##,
+            border = r + 3,
+            href = escape_attribute(&avatar),
+        ));
+    }
+    let names_x =
+        110 + (post.authors.len().max(1) as u32 - 1) * (AVATAR_SIZE - 16) + AVATAR_SIZE + 24;
+    let names = post
+        .authors
+        .iter()
+        .map(|author| author.name.as_str())
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    format!(
+        r##"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" viewBox="0 0 {WIDTH} {HEIGHT}">
+<rect width="{WIDTH}" height="{HEIGHT}" fill="#f1f5f9"/>
+<g fill="#0f172a">{halftone}</g>
+<rect x="60" y="60" width="1080" height="510" rx="16" fill="#f1f5f9" stroke="#0f172a" stroke-width="6"/>
+<text font-size="64" font-weight="800" fill="#0f172a">{title}</text>
+{avatars}
+<text x="{names_x}" y="470" font-size="32" font-weight="600" fill="#0f172a">{names}</text>
+<text x="{names_x}" y="510" font-size="28" fill="#475569">{date}</text>
+<text x="1090" y="510" font-size="32" font-weight="800" fill="#0f172a" text-anchor="end">akosnad.dev</text>
+</svg>"##,
+        halftone = halftone_dots(),
+        names = escape(&names),
+        date = post.metadata.date,
+    )
+}
+
+/// Dots of the halftone pattern, larger where the backdrop gradient is darker
+fn halftone_dots() -> String {
+    let mut dots = String::new();
+    for y in (0..HEIGHT).step_by(DOT_SPACING as usize) {
+        for x in (0..WIDTH).step_by(DOT_SPACING as usize) {
+            // black at the edges, white in the middle, like the backdrop of the site
+            let vertical = (2.0 * y as f32 / HEIGHT as f32 - 1.0).abs();
+            let diagonal = x as f32 / WIDTH as f32;
+            let darkness = (0.8 * vertical + 0.2 * diagonal).clamp(0.0, 1.0);
+            let radius = darkness * DOT_SPACING as f32 / 2.0;
+            if radius >= 0.5 {
+                dots.push_str(&format!(r#"<circle cx="{x}" cy="{y}" r="{radius:.1}"/>"#));
+            }
+        }
+    }
+    dots
+}
+
+/// Title split into lines at word boundaries, shortened with an ellipsis if it's too long
+fn wrap_title(title: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for word in title.split_whitespace() {
+        match lines.last_mut() {
+            Some(line) if line.chars().count() + 1 + word.chars().count() <= TITLE_LINE_LENGTH => {
+                line.push(' ');
+                line.push_str(word);
+            }
+            _ => lines.push(word.to_string()),
+        }
+    }
+    if lines.len() > TITLE_MAX_LINES {
+        lines.truncate(TITLE_MAX_LINES);
+        if let Some(last) = lines.last_mut() {
+            last.push('…');
+        }
+    }
+    lines
+}
+
+/// Path of a file served from `/assets`, `None` for files hosted elsewhere
+fn asset_path(url: &str, site_root: &str) -> Option<PathBuf> {
+    use std::path::Component;
+
+    let relative = Path::new(url.strip_prefix("/assets/")?);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    Some(Path::new(site_root).join(relative))
+}
+
+/// Avatar served from `/assets` inlined as a data URL, `None` for avatars hosted elsewhere
+fn avatar_data_url(url: &str, site_root: &str) -> Option<String> {
+    let path = asset_path(url, site_root)?;
+    let base64 = base64::engine::general_purpose::STANDARD;
+
+    if path.extension().is_some_and(|extension| extension == "svg") {
+        let svg = std::fs::read(&path).ok()?;
+        return Some(format!("data:image/svg+xml;base64,{}", base64.encode(svg)));
+    }
+    // the renderer can't decode every format the site uses, so avatars are converted to PNG
+    let avatar = image::open(&path).ok()?.resize_to_fill(
+        AVATAR_SIZE * 2,
+        AVATAR_SIZE * 2,
+        image::imageops::FilterType::Lanczos3,
+    );
+    let mut png = std::io::Cursor::new(Vec::new());
+    avatar.write_to(&mut png, image::ImageFormat::Png).ok()?;
+    Some(format!(
+        "data:image/png;base64,{}",
+        base64.encode(png.into_inner())
+    ))
+}
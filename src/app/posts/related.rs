@@ -0,0 +1,266 @@
+//! Related posts, by shared tags and similar text
+//!
+//! Every post is compared to every other one when the post index is built, so
+//! showing the related posts of a post is only a lookup.
+
+use super::id::PostId;
+use super::load::PostLoadError;
+#[cfg(feature = "ssr")]
+use super::load::{PostMetadata, read_post_source};
+use super::navigation::PostLink;
+use leptos::prelude::*;
+#[cfg(feature = "ssr")]
+use std::collections::{HashMap, HashSet};
+
+/// Number of related posts shown under a post
+#[cfg(feature = "ssr")]
+const RELATED_COUNT: usize = 3;
+
+/// How much shared tags matter compared to similar text, between 0 and 1
+#[cfg(feature = "ssr")]
+const TAG_WEIGHT: f64 = 0.5;
+
+/// Posts less similar than this are not related, even if nothing else is
+#[cfg(feature = "ssr")]
+const MIN_SCORE: f64 = 0.05;
+
+#[server]
+pub async fn load_related_posts(post_id: PostId) -> Result<Vec<PostLink>, PostLoadError> {
+    Ok(super::index::post_index()?
+        .related
+        .get(&post_id.number)
+        .cloned()
+        .unwrap_or_default())
+}
+
+/// The most related posts of every post, by post number
+#[cfg(feature = "ssr")]
+pub fn find_related(posts: &[(PostId, PostMetadata)]) -> HashMap<usize, Vec<PostLink>> {
+    let sources: Vec<String> = posts
+        .iter()
+        .map(|(post_id, _)| read_post_source(post_id).unwrap_or_default())
+        .collect();
+    rank_related(posts, &sources)
+}
+
+/// The most related posts of every post, given the markdown source of each
+#[cfg(feature = "ssr")]
+fn rank_related(
+    posts: &[(PostId, PostMetadata)],
+    sources: &[String],
+) -> HashMap<usize, Vec<PostLink>> {
+    let vectors = tf_idf_vectors(sources);
+    let tags: Vec<HashSet<String>> = posts
+        .iter()
+        .map(|(_, metadata)| metadata.tags.iter().map(|tag| tag.to_lowercase()).collect())
+        .collect();
+
+    posts
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| {
+            let mut scored: Vec<(f64, usize)> = (0..posts.len())
+                .filter(|j| *j != i)
+                .map(|j| {
+                    let score = TAG_WEIGHT * jaccard(&tags[i], &tags[j])
+                        + (1.0 - TAG_WEIGHT) * cosine(&vectors[i], &vectors[j]);
+                    (score, j)
+                })
+                .filter(|(score, _)| *score >= MIN_SCORE)
+                .collect();
+            scored.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+
+            let related = scored
+                .into_iter()
+                .take(RELATED_COUNT)
+                .map(|(_, j)| PostLink {
+                    id: posts[j].0.clone(),
+                    title: posts[j].1.title.clone(),
+                })
+                .collect();
+            (id.number, related)
+        })
+        .collect()
+}
+
+/// TF-IDF weights of the words of every post, words found in every post weigh nothing
+///
+/// Words are taken from the markdown source, markup is split off like punctuation.
+#[cfg(feature = "ssr")]
+fn tf_idf_vectors(sources: &[String]) -> Vec<HashMap<String, f64>> {
+    let term_counts: Vec<HashMap<String, usize>> = sources
+        .iter()
+        .map(|source| {
+            let mut counts = HashMap::new();
+            for word in source
+                .split(|c: char| !c.is_alphanumeric())
+                .filter(|word| word.chars().count() > 2)
+            {
+                *counts.entry(word.to_lowercase()).or_default() += 1;
+            }
+            counts
+        })
+        .collect();
+
+    let mut document_frequency: HashMap<&str, usize> = HashMap::new();
+    for counts in &term_counts {
+        for word in counts.keys() {
+            *document_frequency.entry(word).or_default() += 1;
+        }
+    }
+
+    let post_count = sources.len() as f64;
+    term_counts
+        .iter()
+        .map(|counts| {
+            let total = counts.values().sum::<usize>().max(1) as f64;
+            counts
+                .iter()
+                .map(|(word, count)| {
+                    let idf = (post_count / document_frequency[word.as_str()] as f64).ln();
+                    (word.clone(), *count as f64 / total * idf)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(feature = "ssr")]
+fn cosine(a: &HashMap<String, f64>, b: &HashMap<String, f64>) -> f64 {
+    let dot: f64 = a
+        .iter()
+        .filter_map(|(word, weight)| Some(weight * b.get(word)?))
+        .sum();
+    let norm = |v: &HashMap<String, f64>| v.values().map(|w| w * w).sum::<f64>().sqrt();
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 { 0.0 } else { dot / norms }
+}
+
+#[cfg(feature = "ssr")]
+fn jaccard(a: &HashSet<String>, b: &HashSet<String>) -> f64 {
+    let union = a.union(b).count();
+    if union == 0 {
+        0.0
+    } else {
+        a.intersection(b).count() as f64 / union as f64
+    }
+}
+
+/// Posts to read after this one
+#[component]
+pub fn RelatedPosts(post_id: PostId) -> impl IntoView {
+    let related = Resource::new(move || post_id.clone(), load_related_posts);
+
+    view! {
+        <Suspense>
+            {move || {
+                related
+                    .get()
+                    .and_then(Result::ok)
+                    .filter(|posts| !posts.is_empty())
+                    .map(|posts| {
+                        view! {
+                            <section id="related-posts" aria-label="Related posts">
+                                <h2 class="text-2xl font-bold">"Related posts"</h2>
+                                <ul>
+                                    {posts
+                                        .into_iter()
+                                        .map(|post| {
+                                            view! {
+                                                <li>
+                                                    <a href=format!("/posts/{}", post.id)>{post.title}</a>
+                                                </li>
+                                            }
+                                        })
+                                        .collect::<Vec<_>>()}
+                                </ul>
+                            </section>
+                        }
+                    })
+            }}
+        </Suspense>
+    }
+}
+
+#[cfg(all(test, feature = "ssr"))]
+mod tests {
+    use super::super::load::read_metadata;
+    use super::*;
+
+    /// Posts with their sources, from `(number, title, tags, body)`
+    fn posts(posts: &[(usize, &str, &str, &str)]) -> (Vec<(PostId, PostMetadata)>, Vec<String>) {
+        posts
+            .iter()
+            .map(|(number, title, tags, body)| {
+                let source = format!(
+                    "---\ntitle: {title}\ndescription: A post\ndate: 2025-07-19\ntags: [{tags}]\n---\n\n{body}\n"
+                );
+                let post_id = PostId {
+                    number: *number,
+                    slug: title.to_lowercase().replace(' ', "-"),
+                };
+                ((post_id, read_metadata(&source).unwrap()), source)
+            })
+            .unzip()
+    }
+
+    fn related_titles(related: &HashMap<usize, Vec<PostLink>>, number: usize) -> Vec<&str> {
+        related[&number]
+            .iter()
+            .map(|post| post.title.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn posts_sharing_tags_are_related() {
+        let (posts, sources) = posts(&[
+            (1, "Ownership", "rust", "Values have one owner."),
+            (2, "Traits", "rust", "Shared behaviour of types."),
+            (3, "Sourdough", "baking", "Flour, water and time."),
+        ]);
+        let related = rank_related(&posts, &sources);
+        assert_eq!(related_titles(&related, 1), ["Traits"]);
+        assert_eq!(related_titles(&related, 2), ["Ownership"]);
+        assert!(related[&3].is_empty());
+    }
+
+    #[test]
+    fn posts_with_similar_text_are_related() {
+        let (posts, sources) = posts(&[
+            (
+                1,
+                "Lifetimes",
+                "",
+                "The borrow checker rejects dangling references.",
+            ),
+            (
+                2,
+                "Sourdough",
+                "",
+                "A starter needs flour and water every day.",
+            ),
+            (
+                3,
+                "Borrowing",
+                "",
+                "Mutable references make the borrow checker strict.",
+            ),
+        ]);
+        let related = rank_related(&posts, &sources);
+        assert_eq!(related_titles(&related, 1), ["Borrowing"]);
+        assert_eq!(related_titles(&related, 3), ["Lifetimes"]);
+        assert!(related[&2].is_empty());
+    }
+
+    #[test]
+    fn words_in_every_post_weigh_nothing() {
+        let vectors = tf_idf_vectors(&[
+            "common words here".to_string(),
+            "common words there".to_string(),
+        ]);
+        assert_eq!(vectors[0]["common"], 0.0);
+        assert!(vectors[0]["here"] > 0.0);
+        assert!(!vectors[1].contains_key("here"));
+        assert_eq!(cosine(&vectors[0], &vectors[1]), 0.0);
+    }
+}
@@ -0,0 +1,249 @@
+//! Rendering of mdast straight to HTML
+//!
+//! Nodes are rendered like `markdown::to_html` would render them, unless the
+//! [`Hooks`] passed along render them differently. Raw HTML nodes are written
+//! as they are, so nothing is parsed as markdown twice.
+
+use super::load::PostLoadError;
+use html_escape::{encode_double_quoted_attribute as escape_attribute, encode_text as escape};
+use markdown::mdast::{AlignKind, List, ListItem, Node, Table};
+
+/// Custom rendering of some nodes
+pub trait Hooks {
+    /// Called for every node, returns `None` to render the node the default way
+    fn node(&mut self, node: &Node) -> Result<Option<String>, PostLoadError>;
+
+    /// Called for every list of sibling nodes, returns `None` to render them one by one
+    fn children(&mut self, _children: &[Node]) -> Result<Option<String>, PostLoadError> {
+        Ok(None)
+    }
+}
+
+/// Renders a node and everything in it
+pub fn render(node: &Node, hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    match hooks.node(node)? {
+        Some(html) => Ok(html),
+        None => render_default(node, hooks),
+    }
+}
+
+/// Renders a node the default way, without calling [`Hooks::node`] for it
+pub fn render_default(node: &Node, hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    Ok(match node {
+        Node::Root(root) => render_children(&root.children, hooks)?,
+        Node::Paragraph(paragraph) => {
+            format!("<p>{}</p>\n", render_children(&paragraph.children, hooks)?)
+        }
+        Node::Heading(heading) => format!(
+            "<h{depth}>{}</h{depth}>\n",
+            render_children(&heading.children, hooks)?,
+            depth = heading.depth
+        ),
+        Node::Blockquote(blockquote) => format!(
+            "<blockquote>\n{}</blockquote>\n",
+            render_children(&blockquote.children, hooks)?
+        ),
+        Node::List(list) => render_list(list, hooks)?,
+        // list items are rendered by their list, which knows if they are tight
+        Node::ListItem(item) => render_list_item(item, false, hooks)?,
+        Node::Table(table) => render_table(table, hooks)?,
+        // rows and cells are rendered by their table, which knows their alignment
+        Node::TableRow(row) => format!("<tr>{}</tr>\n", render_children(&row.children, hooks)?),
+        Node::TableCell(cell) => format!("<td>{}</td>", render_children(&cell.children, hooks)?),
+        Node::ThematicBreak(_) => "<hr />\n".to_string(),
+        Node::Code(code) => {
+            let class = code
+                .lang
+                .as_deref()
+                .map(|lang| format!(" class=\"language-{}\"", escape_attribute(lang)))
+                .unwrap_or_default();
+            let newline = if code.value.is_empty() { "" } else { "\n" };
+            format!(
+                "<pre><code{class}>{}{newline}</code></pre>\n",
+                escape(&code.value)
+            )
+        }
+        Node::Math(math) => format!(
+            "<pre><code class=\"language-math math-display\">{}</code></pre>\n",
+            escape(&math.value)
+        ),
+        Node::Html(html) => html.value.clone(),
+        Node::FootnoteDefinition(definition) => format!(
+            "<div id=\"{}\">{}</div>\n",
+            escape_attribute(&definition.identifier),
+            render_children(&definition.children, hooks)?
+        ),
+        Node::Text(text) => escape(&text.value).to_string(),
+        Node::Emphasis(emphasis) => {
+            format!("<em>{}</em>", render_children(&emphasis.children, hooks)?)
+        }
+        Node::Strong(strong) => format!(
+            "<strong>{}</strong>",
+            render_children(&strong.children, hooks)?
+        ),
+        Node::Delete(delete) => format!("<del>{}</del>", render_children(&delete.children, hooks)?),
+        Node::InlineCode(code) => format!("<code>{}</code>", escape(&code.value)),
+        Node::InlineMath(math) => format!(
+            "<code class=\"language-math math-inline\">{}</code>",
+            escape(&math.value)
+        ),
+        Node::Break(_) => "<br />\n".to_string(),
+        Node::Link(link) => format!(
+            "<a href=\"{}\"{}>{}</a>",
+            escape_attribute(&link.url),
+            render_title(link.title.as_deref()),
+            render_children(&link.children, hooks)?
+        ),
+        Node::Image(image) => format!(
+            "<img src=\"{}\" alt=\"{}\"{} />",
+            escape_attribute(&image.url),
+            escape_attribute(&image.alt),
+            render_title(image.title.as_deref())
+        ),
+        // references without a definition are rendered as the text they were written as
+        Node::LinkReference(reference) => render_children(&reference.children, hooks)?,
+        Node::ImageReference(reference) => escape(&reference.alt).to_string(),
+        Node::FootnoteReference(reference) => format!(
+            "<sup>{}</sup>",
+            escape(reference.label.as_deref().unwrap_or(&reference.identifier))
+        ),
+        // metadata, definitions, and MDX which is not enabled
+        Node::Definition(_)
+        | Node::Yaml(_)
+        | Node::Toml(_)
+        | Node::MdxjsEsm(_)
+        | Node::MdxFlowExpression(_)
+        | Node::MdxTextExpression(_)
+        | Node::MdxJsxFlowElement(_)
+        | Node::MdxJsxTextElement(_) => String::new(),
+    })
+}
+
+/// Renders a list of sibling nodes
+pub fn render_children(children: &[Node], hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    match hooks.children(children)? {
+        Some(html) => Ok(html),
+        None => render_each(children, hooks),
+    }
+}
+
+/// Renders a list of sibling nodes one by one, without calling [`Hooks::children`]
+pub fn render_each(children: &[Node], hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    children.iter().map(|child| render(child, hooks)).collect()
+}
+
+/// Plain text content of nodes, e.g. for `alt` attributes or heading slugs
+pub fn to_text(children: &[Node]) -> String {
+    children
+        .iter()
+        .map(|child| match child {
+            Node::Text(text) => text.value.clone(),
+            Node::InlineCode(code) => code.value.clone(),
+            Node::InlineMath(math) => math.value.clone(),
+            Node::Image(image) => image.alt.clone(),
+            Node::Break(_) => " ".to_string(),
+            child => to_text(child.children().map(Vec::as_slice).unwrap_or_default()),
+        })
+        .collect()
+}
+
+fn render_title(title: Option<&str>) -> String {
+    title
+        .map(|title| format!(" title=\"{}\"", escape_attribute(title)))
+        .unwrap_or_default()
+}
+
+fn render_list(list: &List, hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    // a list is loose if any of its items is separated by a blank line
+    let loose = list.spread
+        || list.children.iter().any(|child| match child {
+            Node::ListItem(item) => item.spread,
+            _ => false,
+        });
+
+    let mut items = String::new();
+    for child in &list.children {
+        items.push_str(&match (child, hooks.node(child)?) {
+            (_, Some(html)) => html,
+            (Node::ListItem(item), None) => render_list_item(item, !loose, hooks)?,
+            (child, None) => render_default(child, hooks)?,
+        });
+    }
+
+    Ok(match (list.ordered, list.start) {
+        (true, Some(start)) if start != 1 => format!("<ol start=\"{start}\">\n{items}</ol>\n"),
+        (true, _) => format!("<ol>\n{items}</ol>\n"),
+        (false, _) => format!("<ul>\n{items}</ul>\n"),
+    })
+}
+
+fn render_list_item(
+    item: &ListItem,
+    tight: bool,
+    hooks: &mut impl Hooks,
+) -> Result<String, PostLoadError> {
+    let checkbox = match item.checked {
+        Some(true) => "<input type=\"checkbox\" disabled=\"\" checked=\"\" /> ",
+        Some(false) => "<input type=\"checkbox\" disabled=\"\" /> ",
+        None => "",
+    };
+    let content = if tight {
+        // paragraphs of tight lists are not wrapped in `<p>`
+        let mut content = String::new();
+        for child in &item.children {
+            content.push_str(&match child {
+                Node::Paragraph(paragraph) => match hooks.node(child)? {
+                    Some(html) => html,
+                    None => render_children(&paragraph.children, hooks)?,
+                },
+                child => render(child, hooks)?,
+            });
+        }
+        content
+    } else {
+        format!("\n{}", render_children(&item.children, hooks)?)
+    };
+    Ok(format!("<li>{checkbox}{content}</li>\n"))
+}
+
+fn render_table(table: &Table, hooks: &mut impl Hooks) -> Result<String, PostLoadError> {
+    let mut render_row = |row: &Node, tag: &str| {
+        if let Some(html) = hooks.node(row)? {
+            return Ok(html);
+        }
+        let mut cells = String::new();
+        for (i, cell) in row.children().into_iter().flatten().enumerate() {
+            let align = match table.align.get(i) {
+                Some(AlignKind::Left) => " align=\"left\"",
+                Some(AlignKind::Right) => " align=\"right\"",
+                Some(AlignKind::Center) => " align=\"center\"",
+                Some(AlignKind::None) | None => "",
+            };
+            cells.push_str(&match (cell, hooks.node(cell)?) {
+                (_, Some(html)) => html,
+                (Node::TableCell(cell), None) => format!(
+                    "<{tag}{align}>{}</{tag}>\n",
+                    render_children(&cell.children, hooks)?
+                ),
+                (cell, None) => render_default(cell, hooks)?,
+            });
+        }
+        Ok::<_, PostLoadError>(format!("<tr>\n{cells}</tr>\n"))
+    };
+
+    let mut rows = table.children.iter();
+    let head = match rows.next() {
+        Some(row) => format!("<thead>\n{}</thead>\n", render_row(row, "th")?),
+        None => String::new(),
+    };
+    let mut body = String::new();
+    for row in rows {
+        body.push_str(&render_row(row, "td")?);
+    }
+    let body = if body.is_empty() {
+        body
+    } else {
+        format!("<tbody>\n{body}</tbody>\n")
+    };
+    Ok(format!("<table>\n{head}{body}</table>\n"))
+}
@@ -0,0 +1,159 @@
+//! Sanitization of the rendered HTML of posts
+//!
+//! Raw HTML written in posts goes through the markdown compiler untouched, so
+//! everything not on the allowlist below is removed from the final HTML.
+//! The allowlist has to cover the markup generated by the post loader too:
+//! headings, footnotes, highlighted code, KaTeX, icons, images and embeds.
+
+use ammonia::Builder;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+
+/// Tags allowed on top of the defaults of `ammonia`
+const TAGS: &[&str] = &[
+    "picture",
+    "source",
+    "iframe",
+    "input",
+    // icons
+    "svg",
+    "path",
+    "circle",
+    "rect",
+    "line",
+    "polyline",
+    "polygon",
+    "g",
+    // MathML emitted by KaTeX
+    "math",
+    "semantics",
+    "annotation",
+    "mrow",
+    "mi",
+    "mn",
+    "mo",
+    "ms",
+    "mtext",
+    "mspace",
+    "msup",
+    "msub",
+    "msubsup",
+    "mfrac",
+    "msqrt",
+    "mroot",
+    "mover",
+    "munder",
+    "munderover",
+    "mtable",
+    "mtr",
+    "mtd",
+    "mstyle",
+    "mpadded",
+    "mphantom",
+    "menclose",
+];
+
+/// Attributes allowed on every tag
+const GENERIC_ATTRIBUTES: &[&str] = &["class", "id", "title", "aria-hidden", "aria-label"];
+
+/// Attributes allowed on specific tags, on top of the defaults of `ammonia`
+const TAG_ATTRIBUTES: &[(&str, &[&str])] = &[
+    ("a", &["rel"]),
+    ("img", &["loading", "decoding", "style"]),
+    ("source", &["type", "srcset", "sizes"]),
+    ("iframe", &["src", "loading", "allow", "allowfullscreen"]),
+    ("input", &["type", "checked", "disabled"]),
+    (
+        "svg",
+        &[
+            "viewBox",
+            "width",
+            "height",
+            "fill",
+            "stroke",
+            "stroke-width",
+            "stroke-linecap",
+            "stroke-linejoin",
+        ],
+    ),
+    ("path", &["d"]),
+    ("circle", &["cx", "cy", "r"]),
+    ("rect", &["x", "y", "width", "height", "rx", "ry"]),
+    ("line", &["x1", "y1", "x2", "y2"]),
+    ("polyline", &["points"]),
+    ("polygon", &["points"]),
+    ("math", &["xmlns", "display"]),
+    ("annotation", &["encoding"]),
+    ("mi", &["mathvariant"]),
+    (
+        "mo",
+        &[
+            "stretchy",
+            "fence",
+            "separator",
+            "lspace",
+            "rspace",
+            "minsize",
+            "maxsize",
+            "largeop",
+            "movablelimits",
+            "symmetric",
+        ],
+    ),
+    ("mspace", &["width"]),
+    ("mfrac", &["linethickness"]),
+    ("mover", &["accent"]),
+    ("munder", &["accentunder"]),
+    ("munderover", &["accent", "accentunder"]),
+    ("mtable", &["rowspacing", "columnspacing", "columnalign"]),
+    ("mtd", &["columnalign"]),
+    ("mstyle", &["scriptlevel", "displaystyle", "mathcolor"]),
+    (
+        "mpadded",
+        &["width", "height", "depth", "lspace", "voffset"],
+    ),
+    ("menclose", &["notation"]),
+];
+
+/// Embeds are only allowed from these origins
+const IFRAME_ORIGINS: &[&str] = &["https://www.youtube-nocookie.com/"];
+
+static PLACEHOLDER_STYLE_RE: LazyLock<regex::Regex> = LazyLock::new(|| {
+    regex::Regex::new(r"^background-image: url\(data:image/png;base64,[A-Za-z0-9+/=]*\)$").unwrap()
+});
+
+static SANITIZER: LazyLock<Builder<'static>> = LazyLock::new(|| {
+    let mut builder = Builder::default();
+    let mut tag_attributes = builder.clone_tag_attributes();
+    for (tag, attributes) in TAG_ATTRIBUTES {
+        tag_attributes
+            .entry(*tag)
+            .or_insert_with(HashSet::new)
+            .extend(attributes.iter().copied());
+    }
+    builder
+        .add_tags(TAGS)
+        .add_generic_attributes(GENERIC_ATTRIBUTES)
+        .tag_attributes(tag_attributes)
+        // links get their `rel` from the post loader, internal links need `rel="external"`
+        // so they are not handled by the client-side router
+        .link_rel(None)
+        .attribute_filter(|element, attribute, value| match (element, attribute) {
+            ("iframe", "src")
+                if !IFRAME_ORIGINS
+                    .iter()
+                    .any(|origin| value.starts_with(origin)) =>
+            {
+                None
+            }
+            // the only inline style needed is the placeholder of images
+            ("img", "style") if !PLACEHOLDER_STYLE_RE.is_match(value) => None,
+            _ => Some(value.into()),
+        });
+    builder
+});
+
+/// Removes everything not on the allowlist from the HTML of a post
+pub fn sanitize(html: &str) -> String {
+    SANITIZER.clean(html).to_string()
+}
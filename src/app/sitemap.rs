@@ -0,0 +1,34 @@
+//! `sitemap.xml` and `robots.txt` for search engines
+
+use super::posts::{PostLoadError, list_posts};
+use super::site_url;
+
+/// Pages which are not posts, listed in the sitemap without a modification date
+const PAGES: &[&str] = &["/", "/about", "/posts"];
+
+/// Sitemap of the pages and every published post, built from the post index
+pub fn sitemap_xml() -> Result<String, PostLoadError> {
+    let site_url = site_url();
+    let mut urls: Vec<_> = PAGES
+        .iter()
+        .map(|page| format!("<url><loc>{site_url}{page}</loc></url>"))
+        .collect();
+    urls.extend(list_posts()?.into_iter().map(|(post_id, metadata)| {
+        let lastmod = metadata.updated.unwrap_or(metadata.date);
+        format!("<url><loc>{site_url}/posts/{post_id}</loc><lastmod>{lastmod}</lastmod></url>")
+    }));
+
+    Ok(format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n{}\n</urlset>\n",
+        urls.join("\n")
+    ))
+}
+
+/// Allows crawling everything but server functions, and points to the sitemap
+pub fn robots_txt() -> String {
+    format!(
+        "User-agent: *\nDisallow: /api/\nAllow: /\n\nSitemap: {}/sitemap.xml\n",
+        site_url()
+    )
+}
@@ -0,0 +1,228 @@
+//! Static files and HTTP caching
+//!
+//! Files under `/pkg` and `/assets` are served from the site root, using a
+//! precompressed `.br` or `.gz` copy of a file when there is one the client
+//! accepts. Everything else is compressed on the fly by the `Compress`
+//! middleware. Pages that set an `ETag` or `Last-Modified` header are answered
+//! with `304 Not Modified` when the client already has them.
+
+use actix_files::NamedFile;
+use actix_web::body::{BoxBody, MessageBody};
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::{self, AcceptEncoding, ContentEncoding, Encoding, HttpDate};
+use actix_web::http::{Method, StatusCode};
+use actix_web::middleware::Next;
+use actix_web::{HttpMessage as _, HttpRequest, HttpResponse, guard, web};
+use std::path::{Component, Path, PathBuf};
+
+/// Files built with a hash in their name, which never change
+pub const IMMUTABLE: &str = "public, max-age=31536000, immutable";
+
+/// Files that keep their name when they change
+pub const REVALIDATE: &str = "public, max-age=3600, must-revalidate";
+
+/// Extensions of precompressed copies, in order of preference when the client
+/// has none
+const PRECOMPRESSED: [(ContentEncoding, &str); 2] = [
+    (ContentEncoding::Brotli, "br"),
+    (ContentEncoding::Gzip, "gz"),
+];
+
+/// Serves the files in `dir` under `mount_path`, with the given `Cache-Control`
+pub fn static_files(
+    mount_path: &str,
+    dir: String,
+    cache_control: &'static str,
+) -> actix_web::Resource {
+    web::resource(format!("{mount_path}/{{path:.*}}")).route(
+        web::route()
+            .guard(guard::Any(guard::Get()).or(guard::Head()))
+            .to(move |req: HttpRequest, path: web::Path<String>| {
+                let file = file_path(Path::new(&dir), &path);
+                async move {
+                    let file = file.ok_or_else(|| actix_web::error::ErrorNotFound("Not Found"))?;
+                    let mut response = negotiate_file(&req, &file)?.into_response(&req);
+                    let headers = response.headers_mut();
+                    headers.insert(
+                        header::CACHE_CONTROL,
+                        header::HeaderValue::from_static(cache_control),
+                    );
+                    headers.insert(
+                        header::VARY,
+                        header::HeaderValue::from_static("accept-encoding"),
+                    );
+                    Ok::<_, actix_web::Error>(response)
+                }
+            }),
+    )
+}
+
+/// Path of an existing file under `dir`, `None` for paths leaving `dir`
+fn file_path(dir: &Path, path: &str) -> Option<PathBuf> {
+    let relative = Path::new(path);
+    if !relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_)))
+    {
+        return None;
+    }
+    let file = dir.join(relative);
+    file.is_file().then_some(file)
+}
+
+/// The file, or one of its precompressed copies if the client accepts it
+fn negotiate_file(req: &HttpRequest, file: &Path) -> std::io::Result<NamedFile> {
+    let original = NamedFile::open(file)?;
+
+    let available: Vec<(Encoding, PathBuf)> = PRECOMPRESSED
+        .iter()
+        .map(|(encoding, extension)| {
+            let mut compressed = file.as_os_str().to_owned();
+            compressed.push(format!(".{extension}"));
+            (Encoding::Known(*encoding), PathBuf::from(compressed))
+        })
+        .filter(|(_, compressed)| compressed.is_file())
+        .collect();
+    // without an `Accept-Encoding` header the client may not decode anything
+    let Some(accepted) = req.get_header::<AcceptEncoding>() else {
+        return Ok(original);
+    };
+    let supported = [Encoding::identity()];
+    let chosen = accepted.negotiate(
+        supported
+            .iter()
+            .chain(available.iter().map(|(encoding, _)| encoding)),
+    );
+
+    match available
+        .into_iter()
+        .find(|(encoding, _)| Some(encoding) == chosen.as_ref())
+    {
+        Some((Encoding::Known(encoding), compressed)) => Ok(NamedFile::open(compressed)?
+            .set_content_type(original.content_type().clone())
+            .set_content_disposition(original.content_disposition().clone())
+            .set_content_encoding(encoding)),
+        _ => Ok(original),
+    }
+}
+
+/// Answers conditional `GET` and `HEAD` requests with `304 Not Modified` when
+/// the validators of the response match
+pub async fn conditional_get(
+    req: ServiceRequest,
+    next: Next<impl MessageBody + 'static>,
+) -> Result<ServiceResponse<BoxBody>, actix_web::Error> {
+    let conditional = matches!(*req.method(), Method::GET | Method::HEAD);
+    let if_none_match = req.headers().get(header::IF_NONE_MATCH).cloned();
+    let if_modified_since = req.get_header::<header::IfModifiedSince>();
+
+    let response = next.call(req).await?;
+    if !conditional || response.status() != StatusCode::OK {
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let headers = response.headers();
+    let not_modified = match (if_none_match, headers.get(header::ETAG)) {
+        // `If-Modified-Since` is ignored when the client sent entity tags
+        (Some(if_none_match), etag) => etag.is_some_and(|etag| {
+            if_none_match.to_str().is_ok_and(|tags| {
+                tags.split(',')
+                    .map(str::trim)
+                    .any(|tag| tag == "*" || weak_eq(tag, etag.to_str().unwrap_or_default()))
+            })
+        }),
+        (None, _) => if_modified_since.is_some_and(|since| {
+            headers
+                .get(header::LAST_MODIFIED)
+                .and_then(|modified| modified.to_str().ok()?.parse::<HttpDate>().ok())
+                .is_some_and(|modified| modified <= since.0)
+        }),
+    };
+    if !not_modified {
+        return Ok(response.map_into_boxed_body());
+    }
+
+    let mut not_modified = HttpResponse::NotModified();
+    for name in [
+        header::ETAG,
+        header::LAST_MODIFIED,
+        header::CACHE_CONTROL,
+        header::VARY,
+    ] {
+        if let Some(value) = headers.get(&name) {
+            not_modified.insert_header((name, value.clone()));
+        }
+    }
+    let (req, _) = response.into_parts();
+    Ok(ServiceResponse::new(req, not_modified.finish()))
+}
+
+/// Weak comparison of entity tags, which ignores the `W/` prefix
+fn weak_eq(a: &str, b: &str) -> bool {
+    a.strip_prefix("W/").unwrap_or(a) == b.strip_prefix("W/").unwrap_or(b)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::{App, middleware, test};
+
+    const LAST_MODIFIED: &str = "Sat, 19 Jul 2025 12:00:00 GMT";
+
+    async fn get(headers: &[(header::HeaderName, &str)]) -> ServiceResponse {
+        let app = test::init_service(
+            App::new()
+                .route(
+                    "/",
+                    web::get().to(|| async {
+                        HttpResponse::Ok()
+                            .insert_header((header::ETAG, "\"v1\""))
+                            .insert_header((header::LAST_MODIFIED, LAST_MODIFIED))
+                            .insert_header((header::CACHE_CONTROL, "no-cache"))
+                            .body("page")
+                    }),
+                )
+                .wrap(middleware::from_fn(conditional_get)),
+        )
+        .await;
+        let mut request = test::TestRequest::get().uri("/");
+        for (name, value) in headers {
+            request = request.insert_header((name.clone(), *value));
+        }
+        test::call_service(&app, request.to_request()).await
+    }
+
+    #[actix_web::test]
+    async fn matching_entity_tags_are_not_modified() {
+        for tags in ["\"v1\"", "W/\"v1\"", "\"v0\", \"v1\"", "*"] {
+            let response = get(&[(header::IF_NONE_MATCH, tags)]).await;
+            assert_eq!(response.status(), StatusCode::NOT_MODIFIED, "{tags}");
+            assert_eq!(response.headers().get(header::ETAG).unwrap(), "\"v1\"");
+            assert_eq!(
+                response.headers().get(header::CACHE_CONTROL).unwrap(),
+                "no-cache"
+            );
+        }
+
+        let response = get(&[(header::IF_NONE_MATCH, "\"v0\"")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(test::read_body(response).await, "page");
+    }
+
+    #[actix_web::test]
+    async fn unchanged_pages_since_a_date_are_not_modified() {
+        let response = get(&[(header::IF_MODIFIED_SINCE, LAST_MODIFIED)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let response = get(&[(header::IF_MODIFIED_SINCE, "Fri, 18 Jul 2025 12:00:00 GMT")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // entity tags win over dates
+        let response = get(&[
+            (header::IF_NONE_MATCH, "\"v0\""),
+            (header::IF_MODIFIED_SINCE, LAST_MODIFIED),
+        ])
+        .await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,70 @@
+//! Configuration of the server that can change while it runs
+//!
+//! It's read from the TOML file at `SERVER_CONFIG` when that's set, and every
+//! setting is optional. On `SIGHUP` the file is read again and the posts are
+//! reloaded, so changes are picked up without restarting the server. Only `log`
+//! is applied again then, `shutdown_timeout`, `image_cache` and the settings
+//! read from the environment, like the security headers, need a restart.
+
+use serde::Deserialize;
+use std::io;
+use std::path::PathBuf;
+
+/// Seconds to wait for open requests when shutting down, if not configured
+const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 30;
+
+#[derive(Deserialize, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// what gets logged, in the syntax of `RUST_LOG`, which it overrides
+    pub log: Option<String>,
+    /// seconds to wait for open requests to finish after `SIGTERM`, only read on startup
+    pub shutdown_timeout: Option<u64>,
+    /// directory the generated post images are written to, only read on startup,
+    /// a directory in the system's temporary directory if not set
+    pub image_cache: Option<PathBuf>,
+}
+
+impl ServerConfig {
+    /// Configuration from the `SERVER_CONFIG` file, the defaults if it's not set
+    pub fn load() -> io::Result<Self> {
+        let Ok(path) = std::env::var("SERVER_CONFIG") else {
+            return Ok(Self::default());
+        };
+        let config = std::fs::read_to_string(&path)?;
+        toml::from_str(&config)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{path}: {e}")))
+    }
+
+    pub fn shutdown_timeout(&self) -> u64 {
+        self.shutdown_timeout.unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT)
+    }
+}
+
+/// Reloads the configuration and the posts every time the server gets `SIGHUP`
+///
+/// A configuration that fails to load is reported, and the current one is kept.
+pub async fn reload_on_hangup() -> io::Result<()> {
+    use actix_web::rt::signal::unix::{SignalKind, signal};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+    while hangups.recv().await.is_some() {
+        tracing::info!("reloading configuration and posts");
+        match ServerConfig::load() {
+            Ok(config) => {
+                if let Err(e) = crate::logging::set_filter(config.log.as_deref()) {
+                    tracing::error!("invalid log filter, keeping the current one: {e}");
+                }
+            }
+            Err(e) => tracing::error!("reading configuration failed, keeping the current one: {e}"),
+        }
+
+        // building the index and rendering every post takes a while, so it's kept off the async workers
+        match actix_web::web::block(personal_site::app::reload_posts).await {
+            Ok(Ok(posts)) => tracing::info!(posts, "reloaded posts"),
+            Ok(Err(e)) => tracing::error!("reloading posts failed: {e}"),
+            Err(e) => tracing::error!("reloading posts failed: {e}"),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,122 @@
+//! Static export of the site
+//!
+//! Every page is requested from the same `App` the server runs, without
+//! binding to a port, and written to `<path>/index.html` under the output
+//! directory. `/pkg`, `/assets` and the generated post images are copied next
+//! to the pages, so the exported pages hydrate like served ones. Server
+//! functions are not available on static hosting, so everything a page needs
+//! has to be rendered into it, like resources of routes with `SsrMode::Async`,
+//! and links load the exported file of the next page instead of navigating on
+//! the client.
+
+use actix_web::http::StatusCode;
+use actix_web::test;
+use leptos::config::LeptosOptions;
+use std::io;
+use std::path::Path;
+
+pub async fn export(leptos_options: &LeptosOptions, out_dir: &Path) -> io::Result<()> {
+    let routes = leptos_actix::generate_route_list(personal_site::app::App);
+    let site_root = leptos_options.site_root.to_string();
+
+    let post_ids: Vec<String> = personal_site::app::list_posts()
+        .map_err(io::Error::other)?
+        .into_iter()
+        .map(|(post_id, _)| post_id.to_string())
+        .collect();
+
+    let mut paths = page_paths(&routes, &post_ids, &site_root)?;
+    paths.extend(["/sitemap.xml", "/robots.txt", "/favicon.svg"].map(String::from));
+    paths.extend(post_ids.iter().map(|post_id| format!("/og/{post_id}.png")));
+
+    let app = test::init_service(crate::site(leptos_options.clone(), routes, true)).await;
+    let mut failed = false;
+    for path in paths {
+        let request = test::TestRequest::get().uri(&path).to_request();
+        let response = test::call_service(&app, request).await;
+        let status = response.status();
+        if !status.is_success() && status != StatusCode::NOT_FOUND {
+            eprintln!("{path}: {status}");
+            failed = true;
+            continue;
+        }
+        let body = test::read_body(response).await;
+
+        let relative = path.trim_start_matches('/');
+        // static hosts show `404.html` for every missing page
+        let file = if status == StatusCode::NOT_FOUND {
+            out_dir.join("404.html")
+        // files keep their name, pages are written as the index of a directory
+        } else if Path::new(relative).extension().is_some() {
+            out_dir.join(relative)
+        } else {
+            out_dir.join(relative).join("index.html")
+        };
+        if let Some(dir) = file.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&file, body)?;
+        println!("{path} -> {}", file.display());
+    }
+
+    let site_root = Path::new(&site_root);
+    copy_dir(&site_root.join("pkg"), &out_dir.join("pkg"), &[])?;
+    // the assets are the site root itself, except for the build output
+    copy_dir(site_root, &out_dir.join("assets"), &["pkg"])?;
+    // post images are generated while rendering, preview images are already exported at `/og`
+    let image_cache = personal_site::app::image_cache_dir();
+    if image_cache.exists() {
+        let url = personal_site::app::IMAGE_CACHE_URL.trim_start_matches('/');
+        copy_dir(image_cache, &out_dir.join(url), &["og"])?;
+    }
+
+    if failed {
+        return Err(io::Error::other("some pages failed to render"));
+    }
+    Ok(())
+}
+
+/// Paths of the pages of every route, with the IDs of posts and authors filled in
+fn page_paths(
+    routes: &[leptos_actix::ActixRouteListing],
+    post_ids: &[String],
+    site_root: &str,
+) -> io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+    for route in routes {
+        let path = route.path();
+        let Some((prefix, _)) = path.split_once([':', '{', '*']) else {
+            paths.push(if path.is_empty() { "/" } else { path }.to_string());
+            continue;
+        };
+        let ids: Vec<String> = match prefix {
+            "/posts/" => post_ids.to_vec(),
+            "/authors/" => personal_site::app::load_authors(site_root)
+                .map_err(io::Error::other)?
+                .into_keys()
+                .collect(),
+            _ => {
+                eprintln!("{path}: skipped, values of its parameters are unknown");
+                continue;
+            }
+        };
+        paths.extend(ids.into_iter().map(|id| format!("{prefix}{id}")));
+    }
+    Ok(paths)
+}
+
+fn copy_dir(from: &Path, to: &Path, skip: &[&str]) -> io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        if skip.iter().any(|name| entry.file_name() == *name) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()), &[])?;
+        } else {
+            std::fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Health and readiness of the server
+//!
+//! `/healthz` answers as long as the process does. `/readyz` also checks that
+//! the content the site is served from is in place and that the last build of
+//! the post index had no failing posts, and answers `503 Service Unavailable`
+//! with the failed checks otherwise.
+
+use actix_web::{HttpResponse, web};
+use leptos::config::LeptosOptions;
+use std::io::{self, Read as _, Write as _};
+use std::net::{SocketAddr, TcpStream};
+use std::path::Path;
+use std::time::Duration;
+
+#[actix_web::get("/healthz")]
+pub async fn healthz() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("ok")
+}
+
+#[actix_web::get("/readyz")]
+pub async fn readyz(leptos_options: web::Data<LeptosOptions>) -> actix_web::Result<HttpResponse> {
+    let site_root = leptos_options.site_root.to_string();
+    // the post index is built on first use, which reads files, so it's kept off the async workers
+    let failed = web::block(move || failed_checks(Path::new(&site_root))).await?;
+
+    let status = if failed.is_empty() {
+        "ready"
+    } else {
+        "not ready"
+    };
+    let body = serde_json::json!({ "status": status, "failed": failed });
+    let mut response = if failed.is_empty() {
+        HttpResponse::Ok()
+    } else {
+        HttpResponse::ServiceUnavailable()
+    };
+    Ok(response
+        .insert_header((actix_web::http::header::CACHE_CONTROL, "no-store"))
+        .json(body))
+}
+
+/// Descriptions of the readiness checks that failed
+fn failed_checks(site_root: &Path) -> Vec<String> {
+    let mut failed = Vec::new();
+    if !site_root.is_dir() {
+        failed.push(format!("site root `{}` is missing", site_root.display()));
+    }
+    let posts = site_root.join("posts");
+    if !posts.is_dir() {
+        failed.push(format!("posts directory `{}` is missing", posts.display()));
+    }
+    let font = site_root.join("site-font.woff2");
+    if !font.is_file() {
+        failed.push(format!("site font `{}` is missing", font.display()));
+    }
+
+    match personal_site::app::failing_posts() {
+        Ok(failing_posts) => failed.extend(
+            failing_posts
+                .into_iter()
+                .map(|(post_id, e)| format!("post `{post_id}` fails to load: {e}")),
+        ),
+        Err(e) => failed.push(format!("listing posts failed: {e}")),
+    }
+    failed
+}
+
+/// Asks the server listening on `addr` if it's ready, for container health checks
+///
+/// The server image has no HTTP client, so the request is written by hand.
+pub fn check_ready(addr: SocketAddr) -> io::Result<bool> {
+    // a server listening on every interface can be reached locally
+    let addr = match addr {
+        SocketAddr::V4(addr) if addr.ip().is_unspecified() => {
+            SocketAddr::from(([127, 0, 0, 1], addr.port()))
+        }
+        SocketAddr::V6(addr) if addr.ip().is_unspecified() => {
+            SocketAddr::from((std::net::Ipv6Addr::LOCALHOST, addr.port()))
+        }
+        addr => addr,
+    };
+    let timeout = Duration::from_secs(10);
+    let mut stream = TcpStream::connect_timeout(&addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    write!(
+        stream,
+        "GET /readyz HTTP/1.0\r\nHost: {addr}\r\nConnection: close\r\n\r\n"
+    )?;
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response)?;
+    let (status_line, body) = response.split_once("\r\n\r\n").unwrap_or((&response, ""));
+    let ready = status_line
+        .lines()
+        .next()
+        .is_some_and(|line| line.split_whitespace().nth(1) == Some("200"));
+    if !ready {
+        tracing::warn!(response = body.trim(), "server is not ready");
+    }
+    Ok(ready)
+}
@@ -5,5 +5,6 @@ pub mod app;
 pub fn hydrate() {
     use app::*;
     console_error_panic_hook::set_once();
+    follow_links_without_router();
     leptos::mount::hydrate_body(App);
 }
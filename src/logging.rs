@@ -0,0 +1,90 @@
+//! Structured logging of the server
+//!
+//! `RUST_LOG` sets what is logged, in the syntax of `tracing_subscriber`'s
+//! `EnvFilter`, `info` by default. The server configuration can override it,
+//! and change it while the server runs. `LOG_FORMAT=json` logs a JSON object per line
+//! instead of text meant for terminals. Every request gets a span with its
+//! method, path, status and latency, logged when the request is done.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use std::sync::OnceLock;
+use tracing::Instrument as _;
+use tracing_subscriber::fmt::format::FmtSpan;
+use tracing_subscriber::layer::SubscriberExt as _;
+use tracing_subscriber::util::SubscriberInitExt as _;
+use tracing_subscriber::{EnvFilter, Layer as _, Registry, reload};
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Sets up the global logger, with the given filter or the one from the environment
+pub fn init(filter: Option<&str>) {
+    let filter = env_filter(filter).unwrap_or_else(|e| {
+        eprintln!("invalid log filter, logging everything at info level: {e}");
+        EnvFilter::new("info")
+    });
+    let (filter, handle) = reload::Layer::new(filter);
+    let json = std::env::var("LOG_FORMAT").is_ok_and(|format| format.eq_ignore_ascii_case("json"));
+
+    // spans are logged when they close, with how long they took
+    let format = tracing_subscriber::fmt::layer()
+        .with_span_events(FmtSpan::CLOSE)
+        .with_writer(std::io::stderr);
+    let format = if json {
+        format.json().boxed()
+    } else {
+        format.pretty().boxed()
+    };
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(format)
+        .init();
+    FILTER.get_or_init(|| handle);
+}
+
+/// Replaces the filter of the global logger, `None` goes back to the one from the environment
+pub fn set_filter(filter: Option<&str>) -> Result<(), String> {
+    let filter = env_filter(filter).map_err(|e| e.to_string())?;
+    let Some(handle) = FILTER.get() else {
+        return Ok(());
+    };
+    handle.reload(filter).map_err(|e| e.to_string())
+}
+
+fn env_filter(filter: Option<&str>) -> Result<EnvFilter, tracing_subscriber::filter::ParseError> {
+    match filter {
+        Some(filter) => EnvFilter::try_new(filter),
+        None => match std::env::var(EnvFilter::DEFAULT_ENV) {
+            Ok(filter) => EnvFilter::try_new(filter),
+            Err(_) => Ok(EnvFilter::new("info")),
+        },
+    }
+}
+
+/// Wraps every request in a span, recording its status and latency
+pub async fn trace_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let span = tracing::info_span!(
+        "request",
+        method = %req.method(),
+        path = req.path(),
+        status = tracing::field::Empty,
+        latency_ms = tracing::field::Empty,
+    );
+    let start = std::time::Instant::now();
+
+    let result = next.call(req).instrument(span.clone()).await;
+    let status = match &result {
+        Ok(response) => response.status(),
+        Err(e) => e.as_response_error().status_code(),
+    };
+    span.record("status", status.as_u16());
+    span.record("latency_ms", start.elapsed().as_secs_f64() * 1000.0);
+    if status.is_server_error() {
+        span.in_scope(|| tracing::error!("request failed"));
+    }
+    result
+}
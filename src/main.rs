@@ -1,58 +1,185 @@
+#[cfg(feature = "ssr")]
+mod caching;
+#[cfg(feature = "ssr")]
+mod config;
+#[cfg(feature = "ssr")]
+mod export;
+#[cfg(feature = "ssr")]
+mod health;
+#[cfg(feature = "ssr")]
+mod logging;
+#[cfg(feature = "ssr")]
+mod security;
+#[cfg(feature = "ssr")]
+mod telemetry;
+
 #[cfg(feature = "ssr")]
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    use actix_files::Files;
     use actix_web::*;
     use leptos::config::get_configuration;
-    use leptos::prelude::*;
-    use leptos_actix::{LeptosRoutes, generate_route_list};
-    use leptos_meta::MetaTags;
+    use leptos_actix::generate_route_list;
     use personal_site::app::App;
 
+    let server_config = config::ServerConfig::load()?;
+    logging::init(server_config.log.as_deref());
+    telemetry::init();
+    // the site root is read-only when it's installed, so generated images are kept outside of it
+    if let Some(dir) = &server_config.image_cache {
+        personal_site::app::set_image_cache_dir(dir.clone());
+    }
+
+    // report broken links in posts instead of serving the site, e.g. before deploying
+    if std::env::args().any(|arg| arg == "--check-links") {
+        let broken_links = personal_site::app::check_post_links().map_err(std::io::Error::other)?;
+        for (post_id, broken_link) in &broken_links {
+            eprintln!("{post_id}: {broken_link}");
+        }
+        if !broken_links.is_empty() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let conf = get_configuration(None).unwrap();
+
+    // ask the running server if it's ready and exit accordingly, for container health checks
+    if std::env::args().any(|arg| arg == "--check-ready") {
+        let ready = health::check_ready(conf.leptos_options.site_addr)?;
+        std::process::exit(if ready { 0 } else { 1 });
+    }
+
+    // render every page to plain files instead of serving the site, for static hosting
+    let mut args = std::env::args().skip_while(|arg| arg != "--export");
+    if args.next().is_some() {
+        let out_dir = args.next().unwrap_or_else(|| "target/export".to_string());
+        return export::export(&conf.leptos_options, std::path::Path::new(&out_dir)).await;
+    }
+
     let addr = conf.leptos_options.site_addr;
+    tracing::info!(%addr, "serving the site");
+
+    rt::spawn(async {
+        if let Err(e) = config::reload_on_hangup().await {
+            tracing::error!("listening for SIGHUP failed, reloading is unavailable: {e}");
+        }
+    });
+
+    if let Some(metrics) = telemetry::server()? {
+        rt::spawn(metrics);
+    }
+
+    // SIGTERM stops accepting connections, and open requests get this long to finish
+    HttpServer::new(move || site(conf.leptos_options.clone(), generate_route_list(App), false))
+        .shutdown_timeout(server_config.shutdown_timeout())
+        .bind(&addr)?
+        .run()
+        .await?;
+    tracing::info!("server stopped");
+    Ok(())
+}
 
-    HttpServer::new(move || {
-        let routes = generate_route_list(App);
-        let leptos_options = &conf.leptos_options;
-        let site_root = &leptos_options.site_root.clone().to_string();
-
-        App::new()
-            // serve JS/WASM/CSS from `pkg`
-            .service(Files::new("/pkg", format!("{site_root}/pkg")))
-            // serve other assets from the `assets` directory
-            .service(Files::new("/assets", site_root))
-            // serve the favicon from /favicon.ico
-            .service(favicon)
-            .leptos_routes(routes, {
-                let leptos_options = leptos_options.clone();
-                move || {
-                    view! {
-                        <!DOCTYPE html>
-                        <html lang="en">
-                            <head>
-                                <meta charset="utf-8" />
-                                <meta
-                                    name="viewport"
-                                    content="width=device-width, initial-scale=1"
-                                />
-                                <AutoReload options=leptos_options.clone() />
-                                <HydrationScripts options=leptos_options.clone() />
-                                <MetaTags />
-                            </head>
-                            <body>
-                                <App />
-                            </body>
-                        </html>
-                    }
+/// Every route of the site, shared by the server and the static export
+///
+/// Pages of the static export are marked, so they don't navigate on the client.
+#[cfg(feature = "ssr")]
+fn site(
+    leptos_options: leptos::config::LeptosOptions,
+    routes: Vec<leptos_actix::ActixRouteListing>,
+    static_export: bool,
+) -> actix_web::App<
+    impl actix_web::dev::ServiceFactory<
+        actix_web::dev::ServiceRequest,
+        Config = (),
+        Response = actix_web::dev::ServiceResponse<impl actix_web::body::MessageBody>,
+        Error = actix_web::Error,
+        InitError = (),
+    >,
+> {
+    use actix_web::*;
+    use leptos::prelude::*;
+    use leptos_actix::LeptosRoutes;
+    use leptos_meta::{HashedStylesheet, MetaTags};
+    use personal_site::app::App;
+
+    let site_root = &leptos_options.site_root.clone().to_string();
+    let security_headers = security::SecurityHeaders::from_env();
+    // only files with a hash in their name can be cached forever
+    let pkg_cache_control = if leptos_options.hash_files {
+        caching::IMMUTABLE
+    } else {
+        caching::REVALIDATE
+    };
+
+    App::new()
+        // serve JS/WASM/CSS from `pkg`
+        .service(caching::static_files(
+            "/pkg",
+            format!("{site_root}/pkg"),
+            pkg_cache_control,
+        ))
+        // serve other assets from the `assets` directory
+        .service(caching::static_files(
+            "/assets",
+            site_root.clone(),
+            caching::REVALIDATE,
+        ))
+        // serve generated post images from the image cache
+        .service(caching::static_files(
+            personal_site::app::IMAGE_CACHE_URL,
+            personal_site::app::image_cache_dir()
+                .to_string_lossy()
+                .into_owned(),
+            caching::REVALIDATE,
+        ))
+        // serve the favicon from /favicon.ico
+        .service(favicon)
+        // social preview images of posts, generated on first request
+        .service(post_preview)
+        .service(sitemap)
+        .service(robots)
+        .service(health::healthz)
+        .service(health::readyz)
+        .leptos_routes(routes, {
+            let leptos_options = leptos_options.clone();
+            let security_headers = security_headers.clone();
+            move || {
+                // pages allow their own inline scripts by nonce, which changes with every response
+                if let Some(resp) = use_context::<leptos_actix::ResponseOptions>() {
+                    let policy = security_headers
+                        .content_security_policy(leptos::nonce::use_nonce().as_deref());
+                    resp.insert_header(
+                        http::header::CONTENT_SECURITY_POLICY,
+                        http::header::HeaderValue::from_str(&policy).unwrap(),
+                    );
+                }
+                view! {
+                    <!DOCTYPE html>
+                    <html lang="en">
+                        <head>
+                            <meta charset="utf-8" />
+                            <meta name="viewport" content="width=device-width, initial-scale=1" />
+                            <AutoReload options=leptos_options.clone() />
+                            // id=leptos means cargo-leptos will hot-reload this stylesheet
+                            <HashedStylesheet options=leptos_options.clone() id="leptos" />
+                            <HydrationScripts options=leptos_options.clone() />
+                            <MetaTags />
+                            {static_export
+                                .then(|| view! { <meta name="static-export" content="true" /> })}
+                        </head>
+                        <body>
+                            <App />
+                        </body>
+                    </html>
                 }
-            })
-            .app_data(web::Data::new(leptos_options.to_owned()))
-        //.wrap(middleware::Compress::default())
-    })
-    .bind(&addr)?
-    .run()
-    .await
+            }
+        })
+        .app_data(web::Data::new(leptos_options))
+        .wrap(security_headers.middleware())
+        .wrap(middleware::from_fn(caching::conditional_get))
+        .wrap(middleware::Compress::default())
+        .wrap(middleware::from_fn(telemetry::track_request))
+        .wrap(middleware::from_fn(logging::trace_request))
 }
 
 #[cfg(feature = "ssr")]
@@ -67,6 +194,47 @@ async fn favicon(
     ))?)
 }
 
+#[cfg(feature = "ssr")]
+#[actix_web::get("/og/{post_id}.png")]
+async fn post_preview(
+    post_id: actix_web::web::Path<String>,
+) -> actix_web::Result<actix_files::NamedFile> {
+    use personal_site::app::{PostId, preview_image};
+    use std::str::FromStr as _;
+
+    let post_id = PostId::from_str(&post_id).map_err(actix_web::error::ErrorBadRequest)?;
+    // rendering the image takes a while, so it's kept off the async workers
+    let path = actix_web::web::block(move || preview_image(post_id))
+        .await?
+        .map_err(|e| {
+            let status = e.status_code();
+            actix_web::error::InternalError::new(e, status)
+        })?;
+    Ok(actix_files::NamedFile::open(path)?)
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::get("/sitemap.xml")]
+async fn sitemap() -> actix_web::Result<actix_web::HttpResponse> {
+    let sitemap = actix_web::web::block(personal_site::app::sitemap_xml)
+        .await?
+        .map_err(|e| {
+            let status = e.status_code();
+            actix_web::error::InternalError::new(e, status)
+        })?;
+    Ok(actix_web::HttpResponse::Ok()
+        .content_type("application/xml")
+        .body(sitemap))
+}
+
+#[cfg(feature = "ssr")]
+#[actix_web::get("/robots.txt")]
+async fn robots() -> actix_web::HttpResponse {
+    actix_web::HttpResponse::Ok()
+        .content_type("text/plain")
+        .body(personal_site::app::robots_txt())
+}
+
 #[cfg(not(any(feature = "ssr", feature = "csr")))]
 pub fn main() {
     // no client-side main function
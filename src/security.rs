@@ -0,0 +1,93 @@
+//! Security headers of the server
+//!
+//! Pages get a content security policy with the nonce of their inline scripts,
+//! every other response gets the same policy without one, along with the rest
+//! of the headers.
+
+/// Security headers of every response, configured through the environment
+///
+/// - `CONTENT_SECURITY_POLICY` replaces the default policy, `{nonce}` in it is
+///   replaced by the nonce of the inline scripts of a page
+/// - `HSTS_MAX_AGE` in seconds, HSTS is off when it's `0` or not set outside of
+///   production
+/// - `REFERRER_POLICY` and `PERMISSIONS_POLICY` replace the defaults
+///
+/// They are read once on startup, `SIGHUP` doesn't change them.
+#[derive(Clone, Debug)]
+pub struct SecurityHeaders {
+    content_security_policy: String,
+    hsts_max_age: u64,
+    referrer_policy: String,
+    permissions_policy: String,
+}
+
+impl SecurityHeaders {
+    pub fn from_env() -> Self {
+        use std::env::var;
+
+        let production = var("LEPTOS_ENV").is_ok_and(|env| env.eq_ignore_ascii_case("prod"));
+        // only scripts carrying the nonce of the page run, and whatever they load. Posts
+        // can contain raw HTML, so their scripts and event handler attributes are blocked.
+        // Styles are allowed inline for the markup of math and highlighted code.
+        // Frames are only allowed for the YouTube embeds of posts.
+        let mut default_policy = String::from(
+            "default-src 'self'; \
+            script-src 'nonce-{nonce}' 'strict-dynamic' 'wasm-unsafe-eval'; \
+            style-src 'self' 'unsafe-inline'; \
+            img-src 'self' data: https:; \
+            frame-src https://www.youtube-nocookie.com; \
+            object-src 'none'; \
+            base-uri 'self'; \
+            form-action 'self'; \
+            frame-ancestors 'none'",
+        );
+        // `cargo leptos watch` reloads pages through a websocket on another port
+        if var("LEPTOS_WATCH").is_ok() {
+            default_policy.push_str("; connect-src 'self' ws: wss:");
+        }
+
+        Self {
+            content_security_policy: var("CONTENT_SECURITY_POLICY").unwrap_or(default_policy),
+            hsts_max_age: var("HSTS_MAX_AGE")
+                .ok()
+                .and_then(|max_age| max_age.parse().ok())
+                .unwrap_or(if production { 365 * 24 * 60 * 60 } else { 0 }),
+            referrer_policy: var("REFERRER_POLICY")
+                .unwrap_or_else(|_| "strict-origin-when-cross-origin".to_string()),
+            permissions_policy: var("PERMISSIONS_POLICY").unwrap_or_else(|_| {
+                "camera=(), microphone=(), geolocation=(), payment=(), usb=(), interest-cohort=()"
+                    .to_string()
+            }),
+        }
+    }
+
+    /// Content security policy with the nonce of the page, responses without
+    /// one allow no inline scripts
+    pub fn content_security_policy(&self, nonce: Option<&str>) -> String {
+        match nonce {
+            Some(nonce) => self.content_security_policy.replace("{nonce}", nonce),
+            None => self.content_security_policy.replace(" 'nonce-{nonce}'", ""),
+        }
+    }
+
+    /// Middleware adding the headers to responses that don't set them already
+    pub fn middleware(&self) -> actix_web::middleware::DefaultHeaders {
+        use actix_web::http::header;
+
+        let mut headers = actix_web::middleware::DefaultHeaders::new()
+            .add((
+                header::CONTENT_SECURITY_POLICY,
+                self.content_security_policy(None),
+            ))
+            .add((header::X_CONTENT_TYPE_OPTIONS, "nosniff"))
+            .add((header::REFERRER_POLICY, self.referrer_policy.clone()))
+            .add(("permissions-policy", self.permissions_policy.clone()));
+        if self.hsts_max_age > 0 {
+            headers = headers.add((
+                header::STRICT_TRANSPORT_SECURITY,
+                format!("max-age={}; includeSubDomains", self.hsts_max_age),
+            ));
+        }
+        headers
+    }
+}
@@ -0,0 +1,110 @@
+//! Prometheus metrics of the server
+//!
+//! Requests are counted and timed by the route they matched, so posts share
+//! one series instead of getting one each. Posts record their own metrics
+//! while loading: cache hits, misses and evictions, how long every stage of
+//! rendering took, and errors by their kind. Everything is served as text from
+//! `/metrics`, but only on the address in `METRICS_ADDR`, apart from the site,
+//! so it can be kept internal. Without it metrics are not served at all.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
+use std::io;
+use std::sync::OnceLock;
+
+static HANDLE: OnceLock<PrometheusHandle> = OnceLock::new();
+
+/// Histogram buckets of durations in seconds, from a millisecond to ten seconds
+const DURATION_BUCKETS: [f64; 12] = [
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 10.0,
+];
+
+/// Sets up the global metrics recorder
+pub fn init() {
+    let handle = PrometheusBuilder::new()
+        .set_buckets_for_metric(
+            Matcher::Suffix("duration_seconds".to_string()),
+            &DURATION_BUCKETS,
+        )
+        .and_then(PrometheusBuilder::install_recorder)
+        .expect("failed to install the metrics recorder");
+    HANDLE.get_or_init(|| handle);
+
+    metrics::describe_counter!("http_requests_total", "Requests by route and status");
+    metrics::describe_histogram!(
+        "http_request_duration_seconds",
+        metrics::Unit::Seconds,
+        "Time until the response of a request started, by route"
+    );
+    metrics::describe_counter!("post_cache_hits_total", "Posts served from the post cache");
+    metrics::describe_counter!("post_cache_misses_total", "Posts rendered on request");
+    metrics::describe_counter!(
+        "post_cache_evictions_total",
+        "Cached posts replaced by a newly rendered version"
+    );
+    metrics::describe_histogram!(
+        "post_render_duration_seconds",
+        metrics::Unit::Seconds,
+        "Time spent in a stage of rendering a post: markdown, tree_sitter or katex"
+    );
+    metrics::describe_counter!("post_load_errors_total", "Failed post loads by error kind");
+}
+
+/// Counts and times every request by its method, route and status
+pub async fn track_request(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let method = req.method().to_string();
+    let start = std::time::Instant::now();
+
+    let response = next.call(req).await?;
+    // the route is only known once the request went through the router
+    let route = response
+        .request()
+        .match_pattern()
+        .unwrap_or_else(|| "unmatched".to_string());
+    let status = response.status().as_u16().to_string();
+
+    metrics::histogram!(
+        "http_request_duration_seconds",
+        "method" => method.clone(),
+        "route" => route.clone(),
+    )
+    .record(start.elapsed().as_secs_f64());
+    metrics::counter!(
+        "http_requests_total",
+        "method" => method,
+        "route" => route,
+        "status" => status,
+    )
+    .increment(1);
+    Ok(response)
+}
+
+/// Server for `/metrics` on `METRICS_ADDR`, `None` if it's not set
+pub fn server() -> io::Result<Option<actix_web::dev::Server>> {
+    let Ok(addr) = std::env::var("METRICS_ADDR") else {
+        return Ok(None);
+    };
+    tracing::info!(%addr, "serving metrics");
+    let server = actix_web::HttpServer::new(|| actix_web::App::new().service(prometheus))
+        .workers(1)
+        .bind(&addr)?
+        .run();
+    Ok(Some(server))
+}
+
+#[actix_web::get("/metrics")]
+pub async fn prometheus() -> actix_web::HttpResponse {
+    let Some(handle) = HANDLE.get() else {
+        return actix_web::HttpResponse::NotFound().finish();
+    };
+    // histograms are only compacted here, scrapes are frequent enough for it
+    handle.run_upkeep();
+    actix_web::HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(handle.render())
+}